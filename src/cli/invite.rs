@@ -0,0 +1,113 @@
+use std::net::SocketAddr;
+
+use clap::Subcommand;
+
+use crate::config::Config;
+use crate::invite::Invite;
+use crate::vault::Vault;
+
+#[derive(Debug, clap::Args)]
+pub struct Args {
+    #[command(subcommand)]
+    command: Command,
+
+    /// Secret shared with the other side to sign and verify codes [default:
+    /// from the vault if its passphrase is in the environment, or unsigned]
+    #[arg(
+        long,
+        global = true,
+        env = "TURN_RELAY_INVITE_SECRET",
+        hide_env_values = true
+    )]
+    secret: Option<String>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Print the invite code of a relay address
+    Encode {
+        /// Relay address, as printed once the relay is allocated
+        relay_addr: SocketAddr,
+
+        /// Name the other side sees the relay as [default: from the
+        /// configuration]
+        #[arg(long)]
+        label: Option<String>,
+
+        /// Local port the other side should forward the traffic of the relay
+        /// to
+        #[arg(long, value_name = "PORT")]
+        fwd: Option<u16>,
+    },
+
+    /// Print the contents of an invite code
+    Decode {
+        code: String,
+
+        /// Print the contents as JSON instead of human-readable text
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+/// Reads the invite secret from the vault, and moves the one written in the
+/// configuration into it. It is only read from the configuration if the vault
+/// cannot be unlocked.
+fn saved_secret(config: &mut Config) -> anyhow::Result<Option<String>> {
+    let Ok(passphrase) = std::env::var("TURN_RELAY_PASSPHRASE") else {
+        return Ok(config.invite_secret.clone());
+    };
+
+    let mut vault = Vault::open_or_create(&passphrase)?;
+
+    if vault.take_invite_secret(config) {
+        vault.save()?;
+        config.save()?;
+    }
+
+    Ok(vault.invite_secret().map(str::to_string))
+}
+
+pub fn run(args: Args) -> anyhow::Result<()> {
+    let mut config = Config::load()?;
+    let secret = match args.secret {
+        Some(i) => Some(i),
+        None => saved_secret(&mut config)?,
+    };
+
+    match args.command {
+        Command::Encode {
+            relay_addr,
+            label,
+            fwd,
+        } => {
+            let invite = Invite {
+                relay_addr,
+                label: label.or(config.invite_label),
+                fwd_port: fwd,
+            };
+
+            println!("{}", invite.encode(secret.as_deref()));
+        }
+
+        Command::Decode { code, json } => {
+            let invite = Invite::decode(&code, secret.as_deref())?;
+
+            if json {
+                println!("{}", serde_json::to_string(&invite)?);
+            } else {
+                println!("Relay address: {}", invite.relay_addr);
+
+                if let Some(label) = &invite.label {
+                    println!("Label: {label}");
+                }
+
+                if let Some(port) = invite.fwd_port {
+                    println!("Forward port: {port}");
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
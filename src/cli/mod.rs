@@ -0,0 +1,30 @@
+mod invite;
+mod run;
+
+use clap::{Parser, Subcommand};
+
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run the relay without a GUI
+    Run(run::Args),
+
+    /// Encode or decode the invite codes of relay addresses
+    Invite(invite::Args),
+}
+
+pub fn run(command: Command) -> anyhow::Result<()> {
+    match command {
+        Command::Run(args) => tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()?
+            .block_on(run::run(args)),
+        Command::Invite(args) => invite::run(args),
+    }
+}
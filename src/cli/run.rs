@@ -0,0 +1,567 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Display;
+use std::net::{AddrParseError, IpAddr, SocketAddr};
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::pin::pin;
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Context};
+use futures::StreamExt;
+use tokio::select;
+use tokio::signal::ctrl_c;
+use tokio::sync::broadcast;
+use turn_relay_worker::{
+    addr, PeerConfig, PortRange, RelayConfig, RelayHandle, RetryPolicy, ReturnMode, ServiceMessage,
+    SourcePolicy, DEFAULT_FWD_SOCKET, LOCAL_IP,
+};
+
+use crate::config::{idle_policy, Config};
+#[cfg(unix)]
+use crate::control;
+use crate::vault::Vault;
+use crate::EVENT_CHANNEL_CAPACITY;
+
+#[derive(Debug, clap::Args)]
+pub struct Args {
+    /// Saved profile to take the server, username and peers from
+    #[arg(long)]
+    profile: Option<String>,
+
+    /// TURN server, as `host` or `host:port`
+    #[arg(long, required_unless_present = "profile")]
+    server: Option<String>,
+
+    /// TURN username
+    #[arg(long, required_unless_present = "profile")]
+    user: Option<String>,
+
+    /// TURN password [default: the saved password of the profile]
+    #[arg(long, env = "TURN_RELAY_PASSWORD", hide_env_values = true)]
+    password: Option<String>,
+
+    /// File descriptor to read the passphrase of saved passwords from, instead
+    /// of the `TURN_RELAY_PASSPHRASE` environment variable
+    #[cfg(unix)]
+    #[arg(long, value_name = "FD")]
+    passphrase_fd: Option<i32>,
+
+    /// Local address to forward the traffic of peers to, as `ip:port`, `port`,
+    /// or `host:port` which is resolved every minute [default: from the
+    /// configuration, or 127.0.0.1:34197]
+    #[arg(long)]
+    forward: Option<String>,
+
+    /// Local address to bind the sockets of unpinned peers on, e.g. 0.0.0.0
+    /// to let other machines reach them [default: from the configuration, or
+    /// 127.0.0.1]
+    #[arg(long)]
+    bind: Option<IpAddr>,
+
+    /// Range of local ports to bind the sockets of unpinned peers on, as
+    /// `start-end` [default: from the configuration, or a dynamic port]
+    #[arg(long, value_name = "RANGE")]
+    ports: Option<PortRange>,
+
+    /// Bind each unpinned peer on the port it had in the previous session, if
+    /// it is free, and save its port in the configuration [default: from the
+    /// configuration]
+    #[arg(long)]
+    stable_ports: bool,
+
+    /// Report peers as idle after this many seconds without traffic [default:
+    /// from the configuration, or never]
+    #[arg(long, value_name = "SECS")]
+    idle: Option<u64>,
+
+    /// Disconnect peers after this many seconds without traffic [default:
+    /// from the configuration, or never]
+    #[arg(long, value_name = "SECS")]
+    remove_idle: Option<u64>,
+
+    /// Retry peers whose permission is denied or whose socket could not be
+    /// bound, up to this many times with an increasing delay [default: from
+    /// the configuration, or never]
+    #[arg(long, value_name = "COUNT")]
+    retry: Option<u32>,
+
+    #[arg(skip)]
+    peer_ports: BTreeMap<SocketAddr, u16>,
+
+    /// Peer to relay, as `ip:port` or `port` (on the relay host), optionally
+    /// pinned to a local address with `=ip:port` or `=port`, and optionally
+    /// forwarded to its own address with `,fwd=ip:port` or `,fwd=port`;
+    /// `,return=learn` sends the traffic of the peer to the last local app
+    /// that sent to it instead, and `,return=learn_locked` to the first one;
+    /// `,allow=fwd` or `,allow=NETWORK` (repeatable) drops local traffic from
+    /// other sources; `,label=NAME` names the peer in the output; Added to the
+    /// peers of the profile, if any
+    #[arg(
+        long = "peer",
+        value_name = "PEER[=LOCAL][,fwd=FWD][,return=MODE][,allow=SOURCE][,label=NAME]"
+    )]
+    peers: Vec<PeerSpec>,
+
+    /// Listen for JSON commands on a Unix socket [default path: from the
+    /// configuration, or `turn_relay.sock` in the runtime directory]
+    #[cfg(unix)]
+    #[allow(clippy::option_option)]
+    #[arg(long, value_name = "PATH")]
+    control_socket: Option<Option<PathBuf>>,
+
+    /// Print events as JSON lines instead of human-readable text
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum PeerAddr {
+    Addr(SocketAddr),
+    Port(u16),
+}
+
+impl PeerAddr {
+    const fn resolve(self, relay_addr: SocketAddr) -> SocketAddr {
+        match self {
+            Self::Addr(i) => i,
+            Self::Port(i) => addr!((relay_addr.ip()):i),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+#[allow(clippy::struct_field_names)]
+struct PeerSpec {
+    peer_addr: PeerAddr,
+    local_addr: Option<SocketAddr>,
+    fwd_addr: Option<SocketAddr>,
+    return_mode: ReturnMode,
+    source_policy: SourcePolicy,
+    label: Option<String>,
+}
+
+impl FromStr for PeerSpec {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut options = s.split(',');
+        let s = options.next().unwrap_or_default();
+        let mut fwd_addr = None;
+        let mut return_mode = ReturnMode::default();
+        let mut source_policy = SourcePolicy::default();
+        let mut label = None;
+
+        for option in options {
+            match option.split_once('=') {
+                Some(("fwd", i)) => fwd_addr = Some(parse_local_addr(i)?),
+                Some(("return", "forward")) => return_mode = ReturnMode::Forward,
+                Some(("return", "learn")) => return_mode = ReturnMode::Learn,
+                Some(("return", "learn_locked")) => return_mode = ReturnMode::LearnLocked,
+                Some(("allow", i)) => match (&mut source_policy, i.parse()?) {
+                    (SourcePolicy::Allow(i), SourcePolicy::Allow(j)) => i.extend(j),
+                    (_, i) => source_policy = i,
+                },
+                Some(("label", i)) => label = Some(i.to_string()),
+                _ => bail!("Unknown peer option {option}"),
+            }
+        }
+
+        let (peer_addr, local_addr) = match s.split_once('=') {
+            Some((peer_addr, local_addr)) => (peer_addr, Some(parse_local_addr(local_addr)?)),
+            None => (s, None),
+        };
+
+        let peer_addr = match peer_addr.parse() {
+            Ok(i) => PeerAddr::Addr(i),
+            Err(e) => PeerAddr::Port(peer_addr.parse().map_err(|_| e)?),
+        };
+
+        Ok(Self {
+            peer_addr,
+            local_addr,
+            fwd_addr,
+            return_mode,
+            source_policy,
+            label,
+        })
+    }
+}
+
+/// Address of a peer, along with its label if it has one.
+struct PeerName<'a>(SocketAddr, Option<&'a str>);
+
+impl Display for PeerName<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.1 {
+            Some(label) => write!(f, "{label} ({})", self.0),
+            None => write!(f, "{}", self.0),
+        }
+    }
+}
+
+impl Args {
+    fn apply_config(&mut self) -> anyhow::Result<()> {
+        let config = match Config::load() {
+            Ok(i) => i,
+            Err(e) if self.profile.is_none() => {
+                eprintln!("Warning: Could not load the configuration: {e:#}");
+                Config::default()
+            }
+            Err(e) => return Err(e),
+        };
+
+        self.bind = self.bind.or(config.bind_ip);
+        self.ports = self.ports.or(config.port_range);
+        self.stable_ports |= config.stable_ports;
+        self.idle = self.idle.or(config.idle_after);
+        self.remove_idle = self.remove_idle.or(config.remove_idle_after);
+        self.retry = self.retry.or(config.retry_attempts);
+        self.peer_ports.clone_from(&config.peer_ports);
+
+        if self.forward.is_none() {
+            self.forward.clone_from(&config.fwd_addr);
+        }
+
+        #[cfg(unix)]
+        if let Some(path @ None) = &mut self.control_socket {
+            *path = Some(
+                config
+                    .control_socket
+                    .clone()
+                    .unwrap_or_else(control::default_path),
+            );
+        }
+
+        let Some(name) = &self.profile else {
+            if self.password.is_none() {
+                bail!("--password is required without --profile");
+            }
+
+            return Ok(());
+        };
+
+        let profile = config
+            .profile(name)
+            .with_context(|| format!("Could not find profile {name}"))?;
+
+        self.server.get_or_insert_with(|| profile.server.clone());
+        self.user.get_or_insert_with(|| profile.username.clone());
+
+        if self.password.is_none() {
+            self.password = Some(
+                Vault::open(&self.read_passphrase()?)?
+                    .password(name)
+                    .with_context(|| format!("Could not find a saved password for {name}"))?
+                    .to_string(),
+            );
+        }
+
+        self.peers.splice(
+            0..0,
+            profile.peers.iter().map(|i| PeerSpec {
+                peer_addr: PeerAddr::Addr(i.peer_addr),
+                local_addr: i.local_addr,
+                fwd_addr: i.fwd_addr,
+                return_mode: i.return_mode,
+                source_policy: i.source_policy.clone(),
+                label: (!i.label.is_empty()).then(|| i.label.clone()),
+            }),
+        );
+
+        Ok(())
+    }
+
+    /// Sets the relay up after it is allocated, and tells the peers by their
+    /// resolved address.
+    fn setup_relay(
+        &self,
+        relay_handle: &RelayHandle,
+        relay_addr: SocketAddr,
+    ) -> anyhow::Result<HashMap<SocketAddr, PeerSpec>> {
+        let mut peers = HashMap::new();
+
+        if let Some(bind_ip) = self.bind {
+            relay_handle.change_bind_ip(bind_ip)?;
+        }
+
+        if self.ports.is_some() {
+            relay_handle.change_port_range(self.ports)?;
+        }
+
+        if let Some(max_attempts) = self.retry {
+            relay_handle.change_retry_policy(Some(RetryPolicy::new(max_attempts)))?;
+        }
+
+        relay_handle.change_fwd_target(
+            self.forward
+                .as_deref()
+                .map_or_else(|| DEFAULT_FWD_SOCKET.to_string(), fwd_target),
+        )?;
+
+        for peer in &self.peers {
+            let peer_addr = peer.peer_addr.resolve(relay_addr);
+            peers.insert(peer_addr, peer.clone());
+
+            relay_handle.connect_peer(
+                PeerConfig::new(peer_addr)
+                    .with_local_addr(peer.local_addr)
+                    .with_fwd_addr(peer.fwd_addr)
+                    .with_return_mode(peer.return_mode)
+                    .with_source_policy(peer.source_policy.clone())
+                    .with_preferred_port(
+                        self.stable_ports
+                            .then(|| self.peer_ports.get(&peer_addr).copied())
+                            .flatten(),
+                    )
+                    .with_label(peer.label.clone())
+                    .with_idle_policy(idle_policy(self.idle, self.remove_idle)),
+            )?;
+        }
+
+        Ok(peers)
+    }
+
+    fn read_passphrase(&self) -> anyhow::Result<String> {
+        #[cfg(unix)]
+        if let Some(fd) = self.passphrase_fd {
+            use std::io::Read;
+            use std::mem::ManuallyDrop;
+            use std::os::fd::FromRawFd;
+
+            let mut passphrase = String::new();
+
+            // SAFETY: The descriptor is opened by the caller, and it is only
+            // read here. It is not closed, since it may be e.g. the standard
+            // input.
+            ManuallyDrop::new(unsafe { std::fs::File::from_raw_fd(fd) })
+                .read_to_string(&mut passphrase)?;
+
+            return Ok(passphrase.trim_end_matches(['\r', '\n']).to_string());
+        }
+
+        std::env::var("TURN_RELAY_PASSPHRASE").context(
+            "Set TURN_RELAY_PASSPHRASE or pass --passphrase-fd to unlock the saved passwords",
+        )
+    }
+}
+
+fn save_peer_port(peer_addr: SocketAddr, port: u16) -> anyhow::Result<()> {
+    let mut config = Config::load()?;
+    config.peer_ports.insert(peer_addr, port);
+    config.save()
+}
+
+fn fwd_target(s: &str) -> String {
+    s.parse()
+        .map_or_else(|_| s.to_string(), |i| addr!(LOCAL_IP:i).to_string())
+}
+
+fn parse_local_addr(s: &str) -> Result<SocketAddr, AddrParseError> {
+    s.parse()
+        .or_else(|e| s.parse().map(|i| addr!(LOCAL_IP:i)).map_err(|_| e))
+}
+
+fn print_event(
+    message: &ServiceMessage,
+    json: bool,
+    peers: &HashMap<SocketAddr, PeerSpec>,
+) -> anyhow::Result<()> {
+    use ServiceMessage as S;
+
+    if json {
+        println!("{}", serde_json::to_string(message)?);
+        return Ok(());
+    }
+
+    let name = |i: &SocketAddr| PeerName(*i, peers.get(i).and_then(|i| i.label.as_deref()));
+
+    match message {
+        S::RelayConnecting(i) => println!("Connecting to {i}"),
+        S::RelayAllocated(i) => println!("Relay is available at {i}"),
+        S::RelayDisconnected => println!("Relay is disconnected"),
+        S::RelayConnectionFailed(why) => {
+            println!("Relay connection failed: {why}; {}", why.hint());
+        }
+        S::RelayRedirected(i) => println!("Relay is redirected to {i}"),
+        S::RelayPeerGranted(i) => println!("Peer {} is authorized", name(i)),
+        S::RelayPeerDenied { peer_addr, error } => {
+            println!(
+                "Peer {} is not authorized: {error}; {}",
+                name(peer_addr),
+                error.hint()
+            );
+        }
+        S::PeerBound {
+            peer_addr,
+            local_addr,
+        } => {
+            println!("Peer {} is bound to {local_addr}", name(peer_addr));
+
+            if !local_addr.ip().is_loopback() {
+                println!(
+                    "Warning: Peer {} is reachable from other machines",
+                    name(peer_addr)
+                );
+            }
+        }
+        S::PeerBindFailed { peer_addr, error } => {
+            println!(
+                "Peer {} failed to bind: {error}; {}",
+                name(peer_addr),
+                error.hint()
+            );
+        }
+        S::PeerRebindFailed {
+            peer_addr,
+            local_addr,
+            error,
+        } => {
+            println!(
+                "Peer {} failed to rebind to {local_addr}: {error}; {}",
+                name(peer_addr),
+                error.hint()
+            );
+        }
+        S::PeerReturnAddr {
+            peer_addr,
+            return_addr,
+        } => println!("Peer {} is returning to {return_addr}", name(peer_addr)),
+        S::PeerDropped { peer_addr, count } => {
+            println!(
+                "Peer {} dropped {count} datagrams from disallowed sources",
+                name(peer_addr)
+            );
+        }
+        S::PeerIdle(i) => println!("Peer {} is idle", name(i)),
+        S::PeerActive(i) => println!("Peer {} is active again", name(i)),
+        S::PeerPaused(i) => println!("Peer {} is paused", name(i)),
+        S::PeerResumed(i) => println!("Peer {} is resumed", name(i)),
+        S::PeerUnbound(i) => println!("Peer {} is unbound", name(i)),
+        S::PeerRetrying { peer_addr, attempt } => {
+            println!("Retrying peer {} (attempt {attempt})", name(peer_addr));
+        }
+        S::FwdAddrChanged(i) => println!("Forwarding to {i}"),
+        S::BindIpChanged(i) => println!("Binding new peers on {i}"),
+        S::PortRangeChanged(Some(i)) => println!("Binding new peers on ports {i}"),
+        S::PortRangeChanged(None) => println!("Binding new peers on dynamic ports"),
+        S::FwdAddrResolved { target, fwd_addr } => {
+            println!("Forwarding to {target} at {fwd_addr}");
+        }
+        S::FwdResolveFailed { target, error } => {
+            println!("Could not resolve {target}: {error}");
+        }
+        // Too frequent to print, or already printed to stderr by the engine.
+        S::PeerTraffic { .. } | S::Log(_) => {}
+        i => println!("{i:?}"),
+    }
+
+    Ok(())
+}
+
+pub async fn run(mut args: Args) -> anyhow::Result<()> {
+    use ServiceMessage as S;
+
+    args.apply_config()?;
+
+    let relay_handle = RelayHandle::new();
+    let mut events = relay_handle.events();
+    let event_snd = broadcast::Sender::<ServiceMessage>::new(EVENT_CHANNEL_CAPACITY);
+
+    #[cfg(unix)]
+    if let Some(Some(path)) = args.control_socket.clone() {
+        let relay_handle = relay_handle.clone();
+        let event_snd = event_snd.clone();
+
+        tokio::spawn(async move {
+            if let Err(e) = control::serve(path, relay_handle, event_snd).await {
+                eprintln!("Control: {e:#}");
+            }
+        });
+    }
+
+    relay_handle.connect_relay(RelayConfig::new(
+        args.server.take().unwrap_or_default(),
+        args.user.take().unwrap_or_default(),
+        args.password.take().unwrap_or_default(),
+    ))?;
+
+    let mut peers = HashMap::new();
+    let mut is_terminating = false;
+    let mut result = Ok(());
+    let mut interrupted = pin!(ctrl_c());
+
+    loop {
+        let message = select! {
+            message = events.next() => message,
+            interrupt = interrupted.as_mut() => {
+                interrupt?;
+                // Listens for the next one.
+                interrupted.set(ctrl_c());
+
+                if is_terminating {
+                    bail!("Interrupted again; Exiting without disconnecting the relay");
+                }
+
+                eprintln!("Interrupted; Disconnecting the relay");
+                relay_handle.terminate()?;
+                is_terminating = true;
+                continue;
+            }
+        };
+
+        let Some(message) = message else {
+            break;
+        };
+
+        print_event(&message, args.json, &peers)?;
+        let _ = event_snd.send(message.clone());
+
+        match message {
+            S::RelayAllocated(relay_addr) => {
+                peers = args.setup_relay(&relay_handle, relay_addr)?;
+                continue;
+            }
+
+            S::PeerBound {
+                peer_addr,
+                local_addr,
+            } => {
+                let port = local_addr.port();
+
+                if args.stable_ports
+                    && peers
+                        .get(&peer_addr)
+                        .is_some_and(|i| i.local_addr.is_none())
+                    && args.peer_ports.insert(peer_addr, port) != Some(port)
+                {
+                    if let Err(e) = save_peer_port(peer_addr, port) {
+                        eprintln!("Warning: Could not save the port of {peer_addr}: {e:#}");
+                    }
+                }
+
+                continue;
+            }
+
+            S::RelayConnectionFailed(why) => {
+                result = Err(anyhow!("Connection to the relay failed: {why}"));
+            }
+
+            S::RelayRedirected(i) => {
+                result = Err(anyhow!(
+                    "Relay is redirected to {i}; Try again with that server"
+                ));
+            }
+
+            S::RelayDisconnected => {}
+
+            _ => continue,
+        }
+
+        if !is_terminating {
+            relay_handle.terminate()?;
+            is_terminating = true;
+        }
+    }
+
+    result
+}
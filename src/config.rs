@@ -0,0 +1,158 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Context};
+use serde::{Deserialize, Serialize};
+use turn_relay_worker::{IdlePolicy, PortRange, ReturnMode, SourcePolicy};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fwd_addr: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bind_ip: Option<IpAddr>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub port_range: Option<PortRange>,
+    #[serde(skip_serializing_if = "is_default")]
+    pub stable_ports: bool,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub peer_ports: BTreeMap<SocketAddr, u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub idle_after: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remove_idle_after: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_attempts: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub invite_label: Option<String>,
+    /// Written by hand, and moved into the vault once it is unlocked.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub invite_secret: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub control_socket: Option<PathBuf>,
+    #[serde(rename = "profile")]
+    pub profiles: Vec<Profile>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    pub name: String,
+    pub server: String,
+    pub username: String,
+    #[serde(rename = "peer")]
+    pub peers: Vec<SavedPeer>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[allow(clippy::struct_field_names)]
+pub struct SavedPeer {
+    pub peer_addr: SocketAddr,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub local_addr: Option<SocketAddr>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fwd_addr: Option<SocketAddr>,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub return_mode: ReturnMode,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub source_policy: SourcePolicy,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub label: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub note: String,
+}
+
+pub fn idle_policy(idle_after: Option<u64>, remove_after: Option<u64>) -> Option<IdlePolicy> {
+    idle_after
+        .or(remove_after)
+        .map(|i| IdlePolicy::new(i, remove_after))
+}
+
+fn is_default<T: Default + PartialEq>(value: &T) -> bool {
+    *value == T::default()
+}
+
+impl Config {
+    pub fn dir() -> anyhow::Result<PathBuf> {
+        Ok(dirs::config_dir()
+            .ok_or_else(|| anyhow!("Could not find the configuration directory"))?
+            .join("turn_relay"))
+    }
+
+    pub fn path() -> anyhow::Result<PathBuf> {
+        Ok(Self::dir()?.join("config.toml"))
+    }
+
+    pub fn load() -> anyhow::Result<Self> {
+        let path = Self::path()?;
+
+        match fs::read_to_string(&path) {
+            Ok(i) => {
+                toml::from_str(&i).with_context(|| format!("Could not parse {}", path.display()))
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e).with_context(|| format!("Could not read {}", path.display())),
+        }
+    }
+
+    pub fn save(&self) -> anyhow::Result<()> {
+        let path = Self::path()?;
+        let temp_path = path.with_extension("toml.tmp");
+
+        fs::create_dir_all(Self::dir()?)?;
+        fs::write(&temp_path, toml::to_string_pretty(self)?)?;
+        fs::rename(&temp_path, &path)?;
+
+        Ok(())
+    }
+
+    pub fn preferred_port(&self, peer_addr: SocketAddr) -> Option<u16> {
+        self.stable_ports
+            .then(|| self.peer_ports.get(&peer_addr).copied())
+            .flatten()
+    }
+
+    pub fn remember_port(&mut self, peer_addr: SocketAddr, port: u16) -> bool {
+        self.stable_ports && self.peer_ports.insert(peer_addr, port) != Some(port)
+    }
+
+    /// Peers are idle after `idle_after`, or after `remove_idle_after` if only
+    /// that is set.
+    pub fn idle_policy(&self) -> Option<IdlePolicy> {
+        idle_policy(self.idle_after, self.remove_idle_after)
+    }
+
+    pub fn profile(&self, name: &str) -> Option<&Profile> {
+        self.profiles.iter().find(|i| i.name == name)
+    }
+
+    pub fn profile_mut(&mut self, name: &str) -> Option<&mut Profile> {
+        self.profiles.iter_mut().find(|i| i.name == name)
+    }
+
+    pub fn profile_names(&self) -> Vec<String> {
+        self.profiles.iter().map(|i| i.name.clone()).collect()
+    }
+
+    pub fn upsert_profile(&mut self, name: &str, server: String, username: String) {
+        if let Some(profile) = self.profile_mut(name) {
+            profile.server = server;
+            profile.username = username;
+        } else {
+            self.profiles.push(Profile {
+                name: name.to_string(),
+                server,
+                username,
+                peers: vec![],
+            });
+        }
+    }
+
+    pub fn remove_profile(&mut self, name: &str) {
+        self.profiles.retain(|i| i.name != name);
+    }
+}
@@ -0,0 +1,204 @@
+use std::fs::{self, DirBuilder, Permissions};
+use std::io::ErrorKind;
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, ensure, Context};
+use futures::{SinkExt, StreamExt};
+use serde_json::json;
+use tokio::net::{UnixListener, UnixStream};
+use tokio::select;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio_util::codec::{Framed, LinesCodec};
+use turn_relay_worker::{CommandMessage, RelayHandle, ServiceMessage};
+
+const MAX_LINE_LENGTH: usize = u16::MAX as usize;
+
+/// Returns a socket in the runtime directory, or else in a directory of the
+/// current user under the temporary directory, which is shared by every user.
+pub fn default_path() -> PathBuf {
+    dirs::runtime_dir()
+        .unwrap_or_else(|| std::env::temp_dir().join(format!("turn_relay-{}", current_uid())))
+        .join("turn_relay.sock")
+}
+
+fn current_uid() -> u32 {
+    // SAFETY: getuid has no preconditions, and it cannot fail.
+    unsafe { libc::getuid() }
+}
+
+/// Creates the directory of the socket if it is missing, and ensures that
+/// only the current user can reach the socket through it.
+fn ensure_private_dir(dir: &Path) -> anyhow::Result<()> {
+    DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(dir)
+        .with_context(|| format!("Could not create {}", dir.display()))?;
+
+    let metadata = fs::symlink_metadata(dir)
+        .with_context(|| format!("Could not inspect {}", dir.display()))?;
+
+    ensure!(metadata.is_dir(), "{} is not a directory", dir.display());
+    ensure!(
+        metadata.uid() == current_uid(),
+        "{} is owned by another user",
+        dir.display()
+    );
+    ensure!(
+        metadata.mode() & 0o777 == 0o700,
+        "{} is accessible by other users; Pick a directory with mode 700",
+        dir.display()
+    );
+
+    Ok(())
+}
+
+struct SocketGuard(PathBuf);
+
+impl Drop for SocketGuard {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+async fn bind(path: &Path) -> anyhow::Result<UnixListener> {
+    ensure_private_dir(
+        path.parent()
+            .filter(|i| !i.as_os_str().is_empty())
+            .unwrap_or_else(|| Path::new(".")),
+    )?;
+
+    if UnixStream::connect(path).await.is_ok() {
+        bail!("{} is used by another instance", path.display());
+    }
+
+    match fs::remove_file(path) {
+        Ok(()) => {}
+        Err(e) if e.kind() == ErrorKind::NotFound => {}
+        Err(e) => Err(e).with_context(|| format!("Could not remove {}", path.display()))?,
+    }
+
+    let listener = UnixListener::bind(path)
+        .with_context(|| format!("Could not listen on {}", path.display()))?;
+
+    fs::set_permissions(path, Permissions::from_mode(0o600))?;
+
+    Ok(listener)
+}
+
+fn handle_line(line: &str, relay_handle: &RelayHandle) -> Option<String> {
+    if line.trim().is_empty() {
+        return None;
+    }
+
+    serde_json::from_str::<CommandMessage>(line)
+        .map_err(anyhow::Error::from)
+        .and_then(|command| {
+            ensure!(
+                !matches!(command, CommandMessage::TerminateAll),
+                "Terminating is not allowed over the control socket"
+            );
+
+            relay_handle.send(command)?;
+            Ok(())
+        })
+        .err()
+        .map(|e| json!({ "error": format!("{e:#}") }).to_string())
+}
+
+async fn handle_client(
+    stream: UnixStream,
+    relay_handle: RelayHandle,
+    mut event_rcv: broadcast::Receiver<ServiceMessage>,
+) {
+    let mut framed = Framed::new(stream, LinesCodec::new_with_max_length(MAX_LINE_LENGTH));
+
+    loop {
+        let reply = select! {
+            line = framed.next() => match line {
+                Some(Ok(line)) => handle_line(&line, &relay_handle),
+                Some(Err(e)) => Some(json!({ "error": format!("{e}") }).to_string()),
+                None => break,
+            },
+            event = event_rcv.recv() => match event {
+                Ok(event) => serde_json::to_string(&event).ok(),
+                Err(RecvError::Lagged(i)) => Some(json!({ "error": format!("Dropped {i} events") }).to_string()),
+                Err(RecvError::Closed) => break,
+            },
+        };
+
+        if let Some(reply) = reply {
+            if framed.send(reply).await.is_err() {
+                break;
+            }
+        }
+    }
+}
+
+pub async fn serve(
+    path: PathBuf,
+    relay_handle: RelayHandle,
+    event_snd: broadcast::Sender<ServiceMessage>,
+) -> anyhow::Result<()> {
+    let listener = bind(&path).await?;
+    let _guard = SocketGuard(path.clone());
+
+    eprintln!("Control: Listening on {}", path.display());
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+
+        tokio::spawn(handle_client(
+            stream,
+            relay_handle.clone(),
+            event_snd.subscribe(),
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("turn_relay-test-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn missing_dir_is_created_private() {
+        let dir = test_dir("missing");
+
+        ensure_private_dir(&dir).unwrap();
+        assert_eq!(fs::metadata(&dir).unwrap().mode() & 0o777, 0o700);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn shared_dir_is_refused() {
+        let dir = test_dir("shared");
+
+        fs::create_dir(&dir).unwrap();
+        fs::set_permissions(&dir, Permissions::from_mode(0o755)).unwrap();
+        assert!(ensure_private_dir(&dir).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn symlinked_dir_is_refused() {
+        let dir = test_dir("target");
+        let link = test_dir("link");
+
+        ensure_private_dir(&dir).unwrap();
+        std::os::unix::fs::symlink(&dir, &link).unwrap();
+        assert!(ensure_private_dir(&link).is_err());
+
+        fs::remove_file(&link).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,119 @@
+use std::net::{IpAddr, SocketAddr};
+
+use iced::widget::{column, text, text_input};
+use iced::Element;
+
+use turn_relay_worker::{addr, LOCAL_IP};
+
+/// Parses a socket address, as `ip:port`, `[ipv6]:port`, or a bare port on
+/// `default_ip`.
+pub fn parse_addr(s: &str, default_ip: IpAddr) -> Result<SocketAddr, String> {
+    let s = s.trim();
+
+    if let Ok(i) = s.parse() {
+        return Ok(i);
+    }
+
+    if !s.is_empty() && s.bytes().all(|i| i.is_ascii_digit()) {
+        return s
+            .parse()
+            .map(|i| addr!((default_ip):i))
+            .map_err(|_| "Port is out of range".to_string());
+    }
+
+    Err("Expected ip:port, [ipv6]:port, or a port".to_string())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Parsed {
+    Addr(SocketAddr),
+    /// A `host:port` that the relay resolves.
+    Host(String),
+}
+
+/// Text input of an address, parsed as the user types, which shows the
+/// parsed address or the error under the field.
+#[derive(Debug, Clone, Default)]
+pub struct AddrInput {
+    value: String,
+    parsed: Option<Result<Parsed, String>>,
+}
+
+impl AddrInput {
+    pub fn parse(value: String, default_ip: IpAddr) -> Self {
+        let parsed =
+            (!value.trim().is_empty()).then(|| parse_addr(&value, default_ip).map(Parsed::Addr));
+
+        Self { value, parsed }
+    }
+
+    /// Parses a forward target, which may also be a `host:port`.
+    pub fn parse_target(value: String) -> Self {
+        let parsed = (!value.trim().is_empty()).then(|| {
+            parse_addr(&value, LOCAL_IP).map(Parsed::Addr).or_else(|e| {
+                let s = value.trim();
+
+                match s.rsplit_once(':') {
+                    Some((host, port))
+                        if !host.is_empty()
+                            && !host.contains([':', '[', ']'])
+                            && !host.contains(char::is_whitespace) =>
+                    {
+                        port.parse::<u16>()
+                            .map(|_| Parsed::Host(s.to_string()))
+                            .map_err(|_| "Invalid port".to_string())
+                    }
+                    _ if s.bytes().all(|i| i.is_ascii_digit()) => Err(e),
+                    _ => Err("Expected ip:port, [ipv6]:port, host:port, or a port".to_string()),
+                }
+            })
+        });
+
+        Self { value, parsed }
+    }
+
+    pub const fn is_valid(&self) -> bool {
+        matches!(self.parsed, Some(Ok(_)))
+    }
+
+    pub const fn is_valid_or_empty(&self) -> bool {
+        !matches!(self.parsed, Some(Err(_)))
+    }
+
+    pub const fn addr(&self) -> Option<SocketAddr> {
+        match self.parsed {
+            Some(Ok(Parsed::Addr(i))) => Some(i),
+            _ => None,
+        }
+    }
+
+    /// Returns the address or the `host:port` to send to the relay.
+    pub fn target(&self) -> Option<String> {
+        match self.parsed.as_ref()? {
+            Ok(Parsed::Addr(i)) => Some(i.to_string()),
+            Ok(Parsed::Host(i)) => Some(i.clone()),
+            Err(_) => None,
+        }
+    }
+
+    pub fn view<'a, M: Clone + 'a>(
+        &'a self,
+        placeholder: &str,
+        on_input: impl Fn(String) -> M + 'a,
+        on_submit: M,
+    ) -> Element<'a, M> {
+        let caption = match &self.parsed {
+            Some(Ok(Parsed::Addr(i))) if i.to_string() != self.value.trim() => text!("{i}"),
+            Some(Err(e)) => text!("{e}").style(text::danger),
+            _ => text!(""),
+        };
+
+        column![
+            text_input(placeholder, &self.value)
+                .on_input(on_input)
+                .on_submit(on_submit),
+            caption.size(12),
+        ]
+        .into()
+    }
+}
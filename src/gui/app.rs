@@ -1,18 +1,29 @@
+#[cfg(unix)]
+use crate::control;
+use crate::gui::relay::traffic;
+use crate::gui::storage::Storage;
 use crate::gui::types::IcedComponent;
-use crate::gui::{peer, relay};
-use crate::worker::{
-    run, CommandMessage, ServiceMessage, COMMAND_CHANNEL_CAPACITY, SERVICE_CHANNEL_CAPACITY,
-};
+use crate::gui::{log, peer, relay};
+use crate::vault::Vault;
+use crate::EVENT_CHANNEL_CAPACITY;
 
-use iced::widget::{center, container};
+use std::future::ready;
+
+use futures::channel::mpsc;
+use futures::future::join;
+use futures::StreamExt;
+use iced::time::every;
+use iced::widget::{center, column, container, text};
 use iced::window::{close, close_requests, Id};
 use iced::{Element, Subscription, Task};
 use tokio::sync::broadcast;
+use turn_relay_worker::{RelayHandle, ServiceMessage};
 
 #[derive(Debug, Clone)]
 pub enum Message {
     OnCloseRequested(Id),
     Relay(relay::Message),
+    Log(log::Message),
 }
 
 impl From<relay::Message> for Message {
@@ -21,13 +32,16 @@ impl From<relay::Message> for Message {
     }
 }
 
-impl From<ServiceMessage> for Message {
-    fn from(value: ServiceMessage) -> Self {
+impl TryFrom<ServiceMessage> for Message {
+    type Error = ServiceMessage;
+
+    fn try_from(value: ServiceMessage) -> Result<Self, Self::Error> {
         use peer::Message as P;
         use relay::Message as R;
         use ServiceMessage as S;
 
-        Self::Relay(match value {
+        Ok(Self::Relay(match value {
+            S::RelayConnecting(server) => R::OnConnecting(server),
             S::RelayAllocated(socket_addr) => R::OnAllocated(socket_addr),
             S::RelayDisconnected => R::OnDisconnected,
             S::RelayConnectionFailed(why) => R::OnConnectionFailed(why),
@@ -35,32 +49,65 @@ impl From<ServiceMessage> for Message {
             S::RelayPeerGranted(socket_addr) => {
                 R::ForPeerByAddr(socket_addr, P::OnPermissionGranted)
             }
-            S::RelayPeerDenied(socket_addr) => R::ForPeerByAddr(socket_addr, P::OnPermissionDenied),
+            S::RelayPeerDenied { peer_addr, error } => {
+                R::ForPeerByAddr(peer_addr, P::OnPermissionDenied(error))
+            }
             S::PeerBound {
                 peer_addr,
                 local_addr,
             } => R::ForPeerByAddr(peer_addr, P::OnBound(local_addr)),
+            S::PeerRebindFailed {
+                peer_addr, error, ..
+            } => R::ForPeerByAddr(peer_addr, P::OnRebindFailed(error)),
+            S::PeerReturnAddr {
+                peer_addr,
+                return_addr,
+            } => R::ForPeerByAddr(peer_addr, P::OnReturnAddr(return_addr)),
+            S::PeerDropped { peer_addr, count } => R::ForPeerByAddr(peer_addr, P::OnDropped(count)),
+            S::PeerTraffic { peer_addr, stats } => R::OnPeerTraffic(peer_addr, stats),
+            S::PeerIdle(socket_addr) => R::ForPeerByAddr(socket_addr, P::OnIdle(true)),
+            S::PeerActive(socket_addr) => R::ForPeerByAddr(socket_addr, P::OnIdle(false)),
+            S::PeerPaused(socket_addr) => R::ForPeerByAddr(socket_addr, P::OnPaused),
+            S::PeerResumed(socket_addr) => R::ForPeerByAddr(socket_addr, P::OnResumed),
+            S::PeerRetrying { peer_addr, .. } => R::ForPeerByAddr(peer_addr, P::OnRetrying),
             S::PeerUnbound(socket_addr) => R::ForPeerByAddr(socket_addr, P::OnUnbound),
-            S::PeerBindFailed(socket_addr) => R::ForPeerByAddr(socket_addr, P::OnBindFailed),
-        })
+            S::PeerBindFailed { peer_addr, error } => {
+                R::ForPeerByAddr(peer_addr, P::OnBindFailed(error))
+            }
+            S::FwdAddrChanged(socket_addr) => R::OnFwdAddrChanged(socket_addr),
+            S::FwdAddrResolved { target, fwd_addr } => R::OnFwdAddrResolved(target, fwd_addr),
+            S::FwdResolveFailed { target, error } => R::OnFwdResolveFailed(target, error),
+            S::BindIpChanged(ip) => R::OnBindIpChanged(ip),
+            S::PortRangeChanged(range) => R::OnPortRangeChanged(range),
+            S::Log(entry) => return Ok(Self::Log(log::Message::OnEntry(entry))),
+            i => return Err(i),
+        }))
     }
 }
 
 #[derive(Debug)]
 pub struct State {
-    command_snd: broadcast::Sender<CommandMessage>,
+    relay_handle: RelayHandle,
     is_relay_connected: bool,
     terminating_window_id: Option<Id>,
+    storage: Storage,
     relay: relay::State,
+    log: log::State,
 }
 
 impl Default for State {
     fn default() -> Self {
         Self {
-            command_snd: broadcast::Sender::<CommandMessage>::new(COMMAND_CHANNEL_CAPACITY),
+            relay_handle: RelayHandle::new(),
             is_relay_connected: false,
             terminating_window_id: None,
-            relay: relay::State::default(),
+            storage: Storage::load(),
+            relay: if Vault::exists() {
+                relay::State::Locked(relay::locked::State::default())
+            } else {
+                relay::State::default()
+            },
+            log: log::State::default(),
         }
     }
 }
@@ -106,39 +153,92 @@ impl IcedComponent for State {
 
                 println!("Got close event for window {id}; Disconnecting the relay");
 
-                self.command_snd.send(CommandMessage::TerminateAll).unwrap();
+                self.relay_handle.terminate().unwrap();
 
                 Task::none()
             }
 
             Message::Relay(sub_message) => self
                 .relay
-                .update(sub_message, &self.command_snd)
+                .update(sub_message, (&self.relay_handle, &mut self.storage))
                 .map(Message::Relay),
+
+            Message::Log(sub_message) => self.log.update(sub_message, ()).map(Message::Log),
         }
     }
 
     fn view<'a>(&'a self, _extra: Self::ExtraViewArgs<'_>) -> Element<'a, Self::Message> {
-        center(container(Element::from(self.relay.view(())).map(Message::Relay)).max_width(512))
+        column![]
+            .push_maybe(self.storage.load_error.as_ref().map(|e| {
+                text!("{e}; Changes will not be saved until the file is fixed").style(text::danger)
+            }))
+            .push(center(
+                container(Element::from(self.relay.view(&self.storage)).map(Message::Relay))
+                    .max_width(512),
+            ))
+            .push(Element::from(self.log.view(())).map(Message::Log))
             .padding(8)
             .into()
     }
 
     fn subscription(&self, _extra: Self::ExtraSubscriptionArgs<'_>) -> Subscription<Self::Message> {
-        let command_snd = self.command_snd.clone();
+        let relay_handle = self.relay_handle.clone();
+        #[cfg(unix)]
+        let control_socket = self.storage.config.control_socket.clone();
 
         Subscription::batch([
-            Subscription::<ServiceMessage>::run_with_id(
+            Subscription::<Message>::run_with_id(
                 (),
                 iced::stream::channel(
-                    SERVICE_CHANNEL_CAPACITY,
-                    move |service_snd: futures::channel::mpsc::Sender<ServiceMessage>| {
-                        run(move || command_snd.subscribe(), service_snd)
+                    EVENT_CHANNEL_CAPACITY,
+                    move |gui_snd: mpsc::Sender<Message>| async move {
+                        let event_snd = broadcast::Sender::new(EVENT_CHANNEL_CAPACITY);
+
+                        let events = relay_handle
+                            .events()
+                            .map({
+                                let event_snd = event_snd.clone();
+                                move |i| {
+                                    let _ = event_snd.send(i.clone());
+                                    i
+                                }
+                            })
+                            .filter_map(|i| {
+                                ready(
+                                    Message::try_from(i)
+                                        .inspect_err(|i| eprintln!("Ignoring event {i:?}"))
+                                        .ok()
+                                        .map(Ok),
+                                )
+                            })
+                            .forward(gui_snd);
+
+                        #[cfg(unix)]
+                        let control = async {
+                            let Some(path) = control_socket else {
+                                return;
+                            };
+
+                            if let Err(e) = control::serve(path, relay_handle, event_snd).await {
+                                eprintln!("Control: Fatal: {e:#}");
+                            }
+                        };
+                        #[cfg(not(unix))]
+                        let control = async move {
+                            let _ = event_snd;
+                        };
+
+                        let _ = join(events, control).await;
                     },
                 ),
-            )
-            .map(Message::from),
+            ),
             close_requests().map(Message::OnCloseRequested),
+            if self.is_relay_connected {
+                every(traffic::SAMPLE_INTERVAL)
+                    .map(|_| Message::Relay(relay::Message::OnTrafficTick))
+            } else {
+                Subscription::none()
+            },
         ])
     }
 }
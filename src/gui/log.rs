@@ -0,0 +1,256 @@
+use std::collections::{BTreeSet, VecDeque};
+use std::fmt::Display;
+use std::fs;
+use std::iter;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, Context};
+use iced::widget::{
+    button, column, horizontal_space, pick_list, row, scrollable, text, text_input, vertical_space,
+    Column,
+};
+use iced::{clipboard, Element, Length, Task};
+
+use turn_relay_worker::{LogEntry, LogLevel};
+
+use crate::gui::types::IcedComponent;
+
+/// Entries beyond this are dropped, oldest first.
+const MAX_ENTRIES: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PeerFilter {
+    All,
+    Peer(SocketAddr),
+}
+
+impl Display for PeerFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::All => write!(f, "All peers"),
+            Self::Peer(i) => write!(f, "{i}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    Toggle,
+    OnEntry(LogEntry),
+    ChangeLevel(LogLevel),
+    ChangePeer(PeerFilter),
+    UpdateSearch(String),
+    CopyAll,
+    Save,
+    Clear,
+}
+
+/// Diagnostics of the engine, which are otherwise only printed to stderr.
+#[derive(Debug)]
+pub struct State {
+    started_at: Instant,
+    entries: VecDeque<(Duration, LogEntry)>,
+    is_open: bool,
+    level: LogLevel,
+    peer: PeerFilter,
+    search: String,
+    saved: Option<Result<PathBuf, String>>,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self {
+            started_at: Instant::now(),
+            entries: VecDeque::new(),
+            is_open: false,
+            level: LogLevel::default(),
+            peer: PeerFilter::All,
+            search: String::new(),
+            saved: None,
+        }
+    }
+}
+
+/// Formats an entry with the time since the GUI started, as `h:mm:ss`.
+fn format_entry((elapsed, entry): &(Duration, LogEntry)) -> String {
+    let secs = elapsed.as_secs();
+    format!(
+        "{}:{:02}:{:02} {entry}",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+impl State {
+    fn filtered(&self) -> impl Iterator<Item = &(Duration, LogEntry)> {
+        let search = self.search.trim().to_lowercase();
+
+        self.entries.iter().filter(move |(_, entry)| {
+            entry.level >= self.level
+                && match self.peer {
+                    PeerFilter::All => true,
+                    PeerFilter::Peer(i) => entry.peer_addr == Some(i),
+                }
+                && (search.is_empty() || entry.to_string().to_lowercase().contains(&search))
+        })
+    }
+
+    fn to_text(&self) -> String {
+        self.filtered().map(|i| format_entry(i) + "\n").collect()
+    }
+
+    fn save(&self) -> anyhow::Result<PathBuf> {
+        let dir = dirs::download_dir()
+            .or_else(dirs::home_dir)
+            .ok_or_else(|| anyhow!("Could not find the downloads directory"))?;
+
+        let path = dir.join(format!(
+            "turn_relay_{}.log",
+            SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs()
+        ));
+
+        fs::write(&path, self.to_text())
+            .with_context(|| format!("Could not write {}", path.display()))?;
+
+        Ok(path)
+    }
+
+    fn view_entries(&self) -> Element<'_, Message> {
+        let peers: Vec<_> = iter::once(PeerFilter::All)
+            .chain(
+                self.entries
+                    .iter()
+                    .filter_map(|(_, i)| i.peer_addr)
+                    .collect::<BTreeSet<_>>()
+                    .into_iter()
+                    .map(PeerFilter::Peer),
+            )
+            .collect();
+
+        let entries = self.filtered().map(|i| {
+            text(format_entry(i))
+                .size(12)
+                .style(match i.1.level {
+                    LogLevel::Info => text::default,
+                    LogLevel::Warning => text::primary,
+                    _ => text::danger,
+                })
+                .into()
+        });
+
+        column![
+            row![
+                pick_list(LogLevel::ALL, Some(self.level), Message::ChangeLevel),
+                horizontal_space().width(8),
+                pick_list(peers, Some(self.peer), Message::ChangePeer),
+                horizontal_space().width(8),
+                text_input("Search", &self.search).on_input(Message::UpdateSearch),
+                horizontal_space().width(8),
+                button(text!("Copy all")).on_press(Message::CopyAll),
+                horizontal_space().width(8),
+                button(text!("Save")).on_press(Message::Save),
+                horizontal_space().width(8),
+                button(text!("Clear")).on_press(Message::Clear),
+            ],
+            vertical_space().height(8),
+            scrollable(Column::with_children(entries).width(Length::Fill))
+                .anchor_bottom()
+                .height(200),
+            match &self.saved {
+                None => text!(""),
+                Some(Ok(path)) => text!("Saved to {}", path.display()),
+                Some(Err(e)) => text!("{e}").style(text::danger),
+            },
+        ]
+        .into()
+    }
+}
+
+impl IcedComponent for State {
+    type Message = Message;
+    type TaskMessage = Message;
+    type ExtraUpdateArgs<'a> = ();
+    type ExtraViewArgs<'a> = ();
+    type ExtraSubscriptionArgs<'a> = ();
+
+    fn update(
+        &mut self,
+        message: Self::Message,
+        _extra: Self::ExtraUpdateArgs<'_>,
+    ) -> Task<Self::TaskMessage> {
+        match message {
+            Message::Toggle => {
+                self.is_open = !self.is_open;
+            }
+
+            Message::OnEntry(i) => {
+                if self.entries.len() >= MAX_ENTRIES {
+                    self.entries.pop_front();
+                }
+
+                self.entries.push_back((self.started_at.elapsed(), i));
+            }
+
+            Message::ChangeLevel(i) => {
+                self.level = i;
+            }
+
+            Message::ChangePeer(i) => {
+                self.peer = i;
+            }
+
+            Message::UpdateSearch(i) => {
+                self.search = i;
+            }
+
+            Message::CopyAll => {
+                return clipboard::write(self.to_text());
+            }
+
+            Message::Save => {
+                self.saved = Some(self.save().map_err(|e| {
+                    eprintln!("Could not save the log: {e:#}");
+                    format!("{e:#}")
+                }));
+            }
+
+            Message::Clear => {
+                self.entries.clear();
+                self.saved = None;
+            }
+        }
+
+        Task::none()
+    }
+
+    fn view<'a>(&'a self, _extra: Self::ExtraViewArgs<'_>) -> Element<'a, Self::Message> {
+        let problem_count = self
+            .entries
+            .iter()
+            .filter(|(_, i)| i.level > LogLevel::Info)
+            .count();
+
+        column![row![
+            button(if self.is_open {
+                text!("Hide log")
+            } else {
+                text!("Show log")
+            })
+            .on_press(Message::Toggle),
+            horizontal_space().width(8),
+            match problem_count {
+                0 => text!(""),
+                1 => text!("1 warning or error").style(text::danger),
+                i => text!("{i} warnings or errors").style(text::danger),
+            },
+        ]]
+        .push_maybe(
+            self.is_open
+                .then(|| column![vertical_space().height(8), self.view_entries()]),
+        )
+        .into()
+    }
+}
@@ -1,7 +1,10 @@
+mod addr_input;
 mod app;
+mod log;
 mod macros;
 mod peer;
 mod relay;
+mod storage;
 mod types;
 
 use iced::{application, window, Settings, Size};
@@ -1,41 +1,81 @@
 use std::net::SocketAddr;
 
 use iced::{
-    widget::{button, horizontal_space, row, text, text_input},
+    widget::{button, horizontal_space, pick_list, row, text, text_input},
     Element, Task,
 };
-use tokio::sync::broadcast;
 
-use crate::{
-    gui::{
-        peer::{failed, ready, waiting},
-        types::IcedComponent,
+use turn_relay_worker::{RelayHandle, ReturnMode, SourcePolicy, LOCAL_IP};
+
+use crate::config::Config;
+use crate::gui::{
+    addr_input::AddrInput,
+    peer::{
+        failed, paused, ready,
+        types::{format_addr, Annotation, SourcePolicyInput},
+        waiting,
     },
-    macros::addr,
-    worker::CommandMessage,
-    LOCAL_IP,
+    types::IcedComponent,
 };
 
 #[derive(Debug, Clone)]
 pub enum Message {
+    UpdateLabel(String),
+    UpdateNote(String),
     UpdateLocal(String),
+    UpdateFwd(String),
+    UpdateReturnMode(ReturnMode),
+    UpdateSourcePolicy(String),
     Setup,
 }
 
 #[derive(Debug, Clone)]
+#[allow(clippy::struct_field_names)]
 pub struct State {
     pub peer_addr: SocketAddr,
-    local_addr: String,
+    local_addr: AddrInput,
+    fwd_addr: AddrInput,
+    pub return_mode: ReturnMode,
+    source_policy: SourcePolicyInput,
+    /// Allowed sources before editing.
+    prev_source_policy: SourcePolicy,
+    pub annotation: Annotation,
+}
+
+impl State {
+    pub const fn pinned_addr(&self) -> Option<SocketAddr> {
+        self.local_addr.addr()
+    }
+
+    pub const fn fwd_addr(&self) -> Option<SocketAddr> {
+        self.fwd_addr.addr()
+    }
+
+    const fn is_valid(&self) -> bool {
+        self.local_addr.is_valid_or_empty()
+            && self.fwd_addr.is_valid_or_empty()
+            && self.source_policy.is_valid()
+    }
+
+    /// Returns the allowed sources, or the ones before editing while the input
+    /// is invalid.
+    pub fn source_policy(&self) -> SourcePolicy {
+        self.source_policy
+            .policy()
+            .unwrap_or_else(|| self.prev_source_policy.clone())
+    }
 }
 
 impl From<waiting::State> for State {
     fn from(value: waiting::State) -> Self {
         Self {
             peer_addr: value.peer_addr,
-            local_addr: value
-                .local_addr
-                .pinned_addr()
-                .map_or_else(String::new, |i| format!("{i}")),
+            local_addr: AddrInput::parse(format_addr(value.local_addr.pinned_addr()), LOCAL_IP),
+            fwd_addr: AddrInput::parse(format_addr(value.fwd_addr), LOCAL_IP),
+            return_mode: value.return_mode,
+            source_policy: SourcePolicyInput::from_policy(&value.source_policy),
+            prev_source_policy: value.source_policy,
+            annotation: value.annotation,
         }
     }
 }
@@ -44,9 +84,12 @@ impl From<failed::State> for State {
     fn from(value: failed::State) -> Self {
         Self {
             peer_addr: value.peer_addr,
-            local_addr: value
-                .pinned_addr
-                .map_or_else(String::new, |i| format!("{i}")),
+            local_addr: AddrInput::parse(format_addr(value.pinned_addr), LOCAL_IP),
+            fwd_addr: AddrInput::parse(format_addr(value.fwd_addr), LOCAL_IP),
+            return_mode: value.return_mode,
+            source_policy: SourcePolicyInput::from_policy(&value.source_policy),
+            prev_source_policy: value.source_policy,
+            annotation: value.annotation,
         }
     }
 }
@@ -55,18 +98,29 @@ impl From<ready::State> for State {
     fn from(value: ready::State) -> Self {
         Self {
             peer_addr: value.peer_addr,
-            local_addr: value
-                .pinned
-                .then_some(value.local_addr)
-                .map_or_else(String::new, |i| format!("{i}")),
+            local_addr: AddrInput::parse(
+                format_addr(value.pinned.then_some(value.local_addr)),
+                LOCAL_IP,
+            ),
+            fwd_addr: AddrInput::parse(format_addr(value.fwd_addr), LOCAL_IP),
+            return_mode: value.return_mode,
+            source_policy: SourcePolicyInput::from_policy(&value.source_policy),
+            prev_source_policy: value.source_policy,
+            annotation: value.annotation,
         }
     }
 }
 
+impl From<paused::State> for State {
+    fn from(value: paused::State) -> Self {
+        Self::from(value.ready)
+    }
+}
+
 impl IcedComponent for State {
     type Message = Message;
     type TaskMessage = super::Message;
-    type ExtraUpdateArgs<'a> = (&'a broadcast::Sender<CommandMessage>, SocketAddr);
+    type ExtraUpdateArgs<'a> = (&'a RelayHandle, SocketAddr, &'a Config);
     type ExtraViewArgs<'a> = usize;
     type ExtraSubscriptionArgs<'a> = ();
 
@@ -76,30 +130,43 @@ impl IcedComponent for State {
         _extra: Self::ExtraUpdateArgs<'_>,
     ) -> Task<Self::TaskMessage> {
         match message {
+            Message::UpdateLabel(i) => {
+                self.annotation.label = i;
+            }
+
+            Message::UpdateNote(i) => {
+                self.annotation.note = i;
+            }
+
             Message::UpdateLocal(i) => {
-                self.local_addr = i;
+                self.local_addr = AddrInput::parse(i, LOCAL_IP);
+            }
+
+            Message::UpdateFwd(i) => {
+                self.fwd_addr = AddrInput::parse(i, LOCAL_IP);
+            }
+
+            Message::UpdateReturnMode(i) => {
+                self.return_mode = i;
+            }
+
+            Message::UpdateSourcePolicy(i) => {
+                self.source_policy = SourcePolicyInput::parse(i);
             }
 
             Message::Setup => {
-                let local_addr = self.local_addr.trim();
-
-                let local_addr =
-                    match (!local_addr.is_empty()).then(|| local_addr.parse::<SocketAddr>()) {
-                        Some(Ok(i)) => Some(i),
-                        Some(Err(e)) => {
-                            if let Ok(i) = local_addr.parse() {
-                                Some(addr!(LOCAL_IP:i))
-                            } else {
-                                eprintln!("Invalid local address {local_addr}: {e}");
-                                return Task::none();
-                            }
-                        }
-                        None => None,
-                    };
+                let Some(source_policy) = self.source_policy.policy().filter(|_| self.is_valid())
+                else {
+                    return Task::none();
+                };
 
                 return Task::done(super::Message::ToWaiting {
                     peer_addr: self.peer_addr,
-                    pinned_addr: local_addr,
+                    pinned_addr: self.local_addr.addr(),
+                    fwd_addr: self.fwd_addr.addr(),
+                    return_mode: self.return_mode,
+                    source_policy,
+                    annotation: Box::new(self.annotation.clone()),
                 });
             }
         }
@@ -111,13 +178,33 @@ impl IcedComponent for State {
         row![
             text!("{})", index + 1).width(48),
             horizontal_space().width(8),
+            text_input("Name", &self.annotation.label)
+                .on_input(Message::UpdateLabel)
+                .on_submit(Message::Setup)
+                .width(120),
+            horizontal_space().width(8),
             text_input("", format!("{}", self.peer_addr).as_ref()),
             horizontal_space().width(8),
-            text_input("127.0.0.1:12345", &self.local_addr)
-                .on_input(Message::UpdateLocal)
+            self.local_addr
+                .view("127.0.0.1:12345", Message::UpdateLocal, Message::Setup),
+            horizontal_space().width(8),
+            self.fwd_addr
+                .view("Forward", Message::UpdateFwd, Message::Setup),
+            horizontal_space().width(8),
+            pick_list(
+                ReturnMode::ALL,
+                Some(self.return_mode),
+                Message::UpdateReturnMode
+            ),
+            horizontal_space().width(8),
+            self.source_policy
+                .view(Message::UpdateSourcePolicy, Message::Setup),
+            horizontal_space().width(8),
+            text_input("Note", &self.annotation.note)
+                .on_input(Message::UpdateNote)
                 .on_submit(Message::Setup),
             horizontal_space().width(8),
-            button(text!("+")).on_press(Message::Setup),
+            button(text!("+")).on_press_maybe(self.is_valid().then_some(Message::Setup)),
         ]
         .into()
     }
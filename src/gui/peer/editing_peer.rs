@@ -1,82 +1,119 @@
 use std::net::SocketAddr;
 
 use iced::{
-    widget::{button, horizontal_space, row, text, text_input},
+    widget::{button, horizontal_space, pick_list, row, text, text_input},
     Element, Task,
 };
-use tokio::sync::broadcast;
 
-use crate::{gui::types::IcedComponent, macros::addr, worker::CommandMessage, LOCAL_IP};
+use turn_relay_worker::{RelayHandle, ReturnMode, LOCAL_IP};
+
+use crate::config::Config;
+use crate::gui::{
+    addr_input::AddrInput,
+    peer::types::{Annotation, SourcePolicyInput},
+    types::IcedComponent,
+};
 
 #[derive(Debug, Clone)]
 pub enum Message {
+    UpdateLabel(String),
+    UpdateNote(String),
     UpdatePeer(String),
     UpdateLocal(String),
+    UpdateFwd(String),
+    UpdateReturnMode(ReturnMode),
+    UpdateSourcePolicy(String),
     Setup,
+    OnDuplicate,
 }
 
 #[derive(Debug, Clone, Default)]
+#[allow(clippy::struct_field_names)]
 pub struct State {
-    pub peer_addr: String,
-    pub local_addr: String,
+    pub peer_addr: AddrInput,
+    pub local_addr: AddrInput,
+    pub fwd_addr: AddrInput,
+    pub return_mode: ReturnMode,
+    pub source_policy: SourcePolicyInput,
+    pub annotation: Annotation,
+    is_duplicate: bool,
+}
+
+impl State {
+    const fn is_valid(&self) -> bool {
+        self.peer_addr.is_valid()
+            && self.local_addr.is_valid_or_empty()
+            && self.fwd_addr.is_valid_or_empty()
+            && self.source_policy.is_valid()
+    }
 }
 
 impl IcedComponent for State {
     type Message = Message;
     type TaskMessage = super::Message;
-    type ExtraUpdateArgs<'a> = (&'a broadcast::Sender<CommandMessage>, SocketAddr);
+    type ExtraUpdateArgs<'a> = (&'a RelayHandle, SocketAddr, &'a Config);
     type ExtraViewArgs<'a> = usize;
     type ExtraSubscriptionArgs<'a> = ();
 
     fn update(
         &mut self,
         message: Self::Message,
-        (_command_snd, relay_addr): Self::ExtraUpdateArgs<'_>,
+        (_relay_handle, relay_addr, _config): Self::ExtraUpdateArgs<'_>,
     ) -> Task<Self::TaskMessage> {
         match message {
+            Message::UpdateLabel(i) => {
+                self.annotation.label = i;
+            }
+
+            Message::UpdateNote(i) => {
+                self.annotation.note = i;
+            }
+
             Message::UpdatePeer(i) => {
-                self.peer_addr = i;
+                self.peer_addr = AddrInput::parse(i, relay_addr.ip());
+                self.is_duplicate = false;
             }
 
             Message::UpdateLocal(i) => {
-                self.local_addr = i;
+                self.local_addr = AddrInput::parse(i, LOCAL_IP);
+            }
+
+            Message::UpdateFwd(i) => {
+                self.fwd_addr = AddrInput::parse(i, LOCAL_IP);
+            }
+
+            Message::UpdateReturnMode(i) => {
+                self.return_mode = i;
+            }
+
+            Message::UpdateSourcePolicy(i) => {
+                self.source_policy = SourcePolicyInput::parse(i);
             }
 
             Message::Setup => {
-                let peer_addr = self.peer_addr.trim();
-
-                let peer_addr = match peer_addr.parse() {
-                    Ok(i) => i,
-                    Err(e) => {
-                        if let Ok(i) = peer_addr.parse() {
-                            addr!((relay_addr.ip()):i)
-                        } else {
-                            eprintln!("Invalid peer address {peer_addr}: {e}");
-                            return Task::none();
-                        }
-                    }
+                let (Some(peer_addr), Some(source_policy)) =
+                    (self.peer_addr.addr(), self.source_policy.policy())
+                else {
+                    return Task::none();
                 };
 
-                let local_addr = self.local_addr.trim();
-
-                let local_addr = match (!local_addr.is_empty()).then(|| local_addr.parse()) {
-                    Some(Ok(i)) => Some(i),
-                    Some(Err(e)) => {
-                        if let Ok(i) = local_addr.parse() {
-                            Some(addr!(LOCAL_IP:i))
-                        } else {
-                            eprintln!("Invalid local address {peer_addr}: {e}");
-                            return Task::none();
-                        }
-                    }
-                    None => None,
-                };
+                if !self.is_valid() {
+                    return Task::none();
+                }
 
                 return Task::done(super::Message::ToWaiting {
                     peer_addr,
-                    pinned_addr: local_addr,
+                    pinned_addr: self.local_addr.addr(),
+                    fwd_addr: self.fwd_addr.addr(),
+                    return_mode: self.return_mode,
+                    source_policy,
+                    annotation: Box::new(self.annotation.clone()),
                 });
             }
+
+            Message::OnDuplicate => {
+                self.is_duplicate = true;
+            }
         }
 
         Task::none()
@@ -85,16 +122,41 @@ impl IcedComponent for State {
     fn view<'a>(&'a self, _index: Self::ExtraViewArgs<'_>) -> Element<'a, Self::Message> {
         row![
             horizontal_space().width(48 + 8),
-            text_input("123.45.67.89:12345", &self.peer_addr)
-                .on_input(Message::UpdatePeer)
-                .on_submit(Message::Setup),
+            text_input("Name", &self.annotation.label)
+                .on_input(Message::UpdateLabel)
+                .on_submit(Message::Setup)
+                .width(120),
+            horizontal_space().width(8),
+            self.peer_addr
+                .view("123.45.67.89:12345", Message::UpdatePeer, Message::Setup),
+            horizontal_space().width(8),
+            self.local_addr
+                .view("127.0.0.1:12345", Message::UpdateLocal, Message::Setup),
+            horizontal_space().width(8),
+            self.fwd_addr
+                .view("Forward", Message::UpdateFwd, Message::Setup),
+            horizontal_space().width(8),
+            pick_list(
+                ReturnMode::ALL,
+                Some(self.return_mode),
+                Message::UpdateReturnMode
+            ),
+            horizontal_space().width(8),
+            self.source_policy
+                .view(Message::UpdateSourcePolicy, Message::Setup),
             horizontal_space().width(8),
-            text_input("127.0.0.1:12345", &self.local_addr)
-                .on_input(Message::UpdateLocal)
+            text_input("Note", &self.annotation.note)
+                .on_input(Message::UpdateNote)
                 .on_submit(Message::Setup),
             horizontal_space().width(8),
-            button(text!("+")).on_press(Message::Setup),
+            button(text!("+")).on_press_maybe(self.is_valid().then_some(Message::Setup)),
         ]
+        .push_maybe(self.is_duplicate.then(|| {
+            row![
+                horizontal_space().width(8),
+                text!("Peer is already added").style(text::danger),
+            ]
+        }))
         .into()
     }
 }
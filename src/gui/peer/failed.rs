@@ -1,47 +1,76 @@
 use std::net::SocketAddr;
 
 use iced::{
-    widget::{button, horizontal_space, row, text, text_input},
+    widget::{button, container, horizontal_space, row, text, text_input, tooltip},
     Element, Length, Task,
 };
-use tokio::sync::broadcast;
 
-use crate::{gui::types::IcedComponent, worker::CommandMessage};
+use turn_relay_worker::{PeerError, RelayHandle, ReturnMode, SourcePolicy};
+
+use crate::config::Config;
+use crate::gui::{peer::types::Annotation, types::IcedComponent};
 
 #[derive(Debug, Clone)]
 pub enum Message {
+    UpdateLabel(String),
+    Retry,
     Delete,
-    OnPermissionDenied,
-    OnBindFailed,
+    OnPermissionDenied(PeerError),
+    OnBindFailed(PeerError),
 }
 
 #[derive(Debug, Clone)]
 pub struct State {
     pub peer_addr: SocketAddr,
     pub pinned_addr: Option<SocketAddr>,
-    permission_denied: bool,
-    bind_failed: bool,
+    pub fwd_addr: Option<SocketAddr>,
+    pub return_mode: ReturnMode,
+    pub source_policy: SourcePolicy,
+    permission_error: Option<PeerError>,
+    bind_error: Option<PeerError>,
+    pub annotation: Annotation,
 }
 
 impl State {
     pub const fn new_permission_denied(
         peer_addr: SocketAddr,
         pinned_addr: Option<SocketAddr>,
+        fwd_addr: Option<SocketAddr>,
+        return_mode: ReturnMode,
+        source_policy: SourcePolicy,
+        annotation: Annotation,
+        error: PeerError,
     ) -> Self {
         Self {
             peer_addr,
             pinned_addr,
-            permission_denied: true,
-            bind_failed: false,
+            fwd_addr,
+            return_mode,
+            source_policy,
+            permission_error: Some(error),
+            bind_error: None,
+            annotation,
         }
     }
 
-    pub const fn new_bind_failed(peer_addr: SocketAddr, pinned_addr: Option<SocketAddr>) -> Self {
+    pub const fn new_bind_failed(
+        peer_addr: SocketAddr,
+        pinned_addr: Option<SocketAddr>,
+        fwd_addr: Option<SocketAddr>,
+        return_mode: ReturnMode,
+        source_policy: SourcePolicy,
+        annotation: Annotation,
+        error: PeerError,
+    ) -> Self {
         Self {
             peer_addr,
             pinned_addr,
-            permission_denied: false,
-            bind_failed: true,
+            fwd_addr,
+            return_mode,
+            source_policy,
+            permission_error: None,
+            bind_error: Some(error),
+            annotation,
         }
     }
 }
@@ -49,32 +78,46 @@ impl State {
 impl IcedComponent for State {
     type Message = Message;
     type TaskMessage = super::Message;
-    type ExtraUpdateArgs<'a> = (&'a broadcast::Sender<CommandMessage>, SocketAddr);
+    type ExtraUpdateArgs<'a> = (&'a RelayHandle, SocketAddr, &'a Config);
     type ExtraViewArgs<'a> = usize;
     type ExtraSubscriptionArgs<'a> = ();
 
     fn update(
         &mut self,
         message: Self::Message,
-        (command_snd, _relay_addr): Self::ExtraUpdateArgs<'_>,
+        (relay_handle, _relay_addr, _config): Self::ExtraUpdateArgs<'_>,
     ) -> Task<Self::TaskMessage> {
         match message {
+            Message::UpdateLabel(i) => {
+                self.annotation.label = i;
+            }
+
+            Message::Retry => {
+                return Task::done(super::Message::ToWaiting {
+                    peer_addr: self.peer_addr,
+                    pinned_addr: self.pinned_addr,
+                    fwd_addr: self.fwd_addr,
+                    return_mode: self.return_mode,
+                    source_policy: self.source_policy.clone(),
+                    annotation: Box::new(self.annotation.clone()),
+                });
+            }
+
             Message::Delete => {
-                if self.bind_failed {
+                // Forgets the peer, and cancels its pending retry.
+                relay_handle.disconnect_peer(self.peer_addr).unwrap();
+
+                if self.bind_error.is_some() {
                     return Task::done(super::Message::ToEditingLocal);
                 }
-
-                command_snd
-                    .send(CommandMessage::DisconnectPeer(self.peer_addr))
-                    .unwrap();
             }
 
-            Message::OnPermissionDenied => {
-                self.permission_denied = true;
+            Message::OnPermissionDenied(e) => {
+                self.permission_error = Some(e);
             }
 
-            Message::OnBindFailed => {
-                self.bind_failed = true;
+            Message::OnBindFailed(e) => {
+                self.bind_error = Some(e);
             }
         }
 
@@ -85,16 +128,25 @@ impl IcedComponent for State {
         row![
             text!("{})", index + 1).width(48),
             horizontal_space().width(8),
+            self.annotation.view(Message::UpdateLabel),
+            horizontal_space().width(8),
             text_input("", format!("{}", self.peer_addr).as_ref()),
             horizontal_space().width(8),
-            if self.permission_denied {
-                text!("Authorization Failed")
-            } else if self.bind_failed {
-                text!("Binding Failed")
-            } else {
-                text!("Failed")
-            }
-            .width(Length::Fill),
+            self.permission_error
+                .as_ref()
+                .or(self.bind_error.as_ref())
+                .map_or_else(
+                    || text!("Failed").width(Length::Fill).into(),
+                    |e| Element::from(tooltip(
+                        text!("{e}").width(Length::Fill),
+                        container(text!("{}", e.hint()))
+                            .padding(4)
+                            .style(container::rounded_box),
+                        tooltip::Position::Bottom,
+                    )),
+                ),
+            horizontal_space().width(8),
+            button(text!("Retry")).on_press(Message::Retry),
             horizontal_space().width(8),
             button(text!("X")).on_press(Message::Delete),
         ]
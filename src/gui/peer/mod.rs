@@ -1,28 +1,44 @@
 mod editing_local;
 mod editing_peer;
 mod failed;
+mod paused;
 mod ready;
-mod types;
+pub mod types;
 mod waiting;
 
 use std::net::SocketAddr;
 
-use tokio::sync::broadcast;
+use turn_relay_worker::{PeerConfig, PeerError, RelayHandle, ReturnMode, SourcePolicy};
 
-use crate::{gui::macros::router_component, worker::CommandMessage};
+use crate::{
+    config::{Config, SavedPeer},
+    gui::{macros::router_component, peer::types::Annotation},
+};
 
 router_component! {
     message enum Message {
-        OnBindFailed,
+        OnBindFailed(PeerError),
         OnBound(SocketAddr),
-        OnPermissionDenied,
+        OnDropped(u64),
+        OnDuplicate,
+        OnIdle(bool),
+        OnPaused,
+        OnPermissionDenied(PeerError),
         OnPermissionGranted,
+        OnRebindFailed(PeerError),
+        OnResumed,
+        OnRetrying,
+        OnReturnAddr(SocketAddr),
         OnUnbound,
         ToEditingLocal,
         ToReady,
         ToWaiting {
             peer_addr: SocketAddr,
             pinned_addr: Option<SocketAddr>,
+            fwd_addr: Option<SocketAddr>,
+            return_mode: ReturnMode,
+            source_policy: SourcePolicy,
+            annotation: Box<Annotation>,
         },
     }
 
@@ -32,33 +48,48 @@ router_component! {
         Waiting(waiting::State),
         Failed(failed::State),
         Ready(ready::State),
+        Paused(paused::State),
     }
 
     impl Default for EditingPeer;
 
-    type ExtraUpdateArgs<'a> = (&'a broadcast::Sender<CommandMessage>, SocketAddr);
+    type ExtraUpdateArgs<'a> = (&'a RelayHandle, SocketAddr, &'a Config);
     type ExtraViewArgs<'a> = usize;
     type ExtraSubscriptionArgs<'a> = ();
 
     fn update(..) {
         // OnBindFailed
         given OnBindFailed ignore EditingPeer;
-        given OnBindFailed ignore EditingLocal;
 
-        given OnBindFailed {}
+        // A row being edited is not connected, so an event means that the peer
+        // was connected again from outside of the GUI, e.g. by the control
+        // socket.
+        given OnBindFailed(e)
+            turn EditingLocal(i)
+            into Failed(failed::State::new_bind_failed(i.peer_addr, i.pinned_addr(), i.fwd_addr(), i.return_mode, i.source_policy(), i.annotation, e));
+
+        given OnBindFailed(e)
             turn Waiting(i)
-            into Failed(failed::State::new_bind_failed(i.peer_addr, i.local_addr.pinned_addr()));
+            into Failed(failed::State::new_bind_failed(i.peer_addr, i.local_addr.pinned_addr(), i.fwd_addr, i.return_mode, i.source_policy, i.annotation, e));
 
-        given OnBindFailed {}
-            pass Failed(failed::Message::OnBindFailed);
+        given OnBindFailed(e)
+            pass Failed(failed::Message::OnBindFailed(e));
 
-        given OnBindFailed {}
+        given OnBindFailed(e)
             turn Ready(i)
-            into Failed(failed::State::new_bind_failed(i.peer_addr, i.pinned.then_some(i.local_addr)));
+            into Failed(failed::State::new_bind_failed(i.peer_addr, i.pinned.then_some(i.local_addr), i.fwd_addr, i.return_mode, i.source_policy, i.annotation, e));
+
+        given OnBindFailed ignore Paused;
 
         // OnBound
         given OnBound ignore EditingPeer;
-        given OnBound ignore EditingLocal;
+
+        given OnBound(a)
+            turn EditingLocal(i)
+            into Waiting(waiting::State::new(i.peer_addr, i.pinned_addr(), i.fwd_addr(), i.return_mode, i.source_policy(), i.annotation))
+            then {
+                return ::iced::Task::done(Message::OnBound(a));
+            };
 
         given OnBound(i)
             pass Waiting(waiting::Message::OnBound(i));
@@ -68,30 +99,133 @@ router_component! {
         given OnBound(i)
             pass Ready(ready::Message::OnBound(i));
 
+        given OnBound(i)
+            pass Paused(paused::Message::OnBound(i));
+
+        // OnDropped
+        given OnDropped ignore EditingPeer;
+        given OnDropped ignore EditingLocal;
+
+        given OnDropped(i)
+            pass Waiting(waiting::Message::OnDropped(i));
+
+        given OnDropped ignore Failed;
+
+        given OnDropped(i)
+            pass Ready(ready::Message::OnDropped(i));
+
+        given OnDropped ignore Paused;
+
+        // OnDuplicate
+        given OnDuplicate {}
+            pass EditingPeer(editing_peer::Message::OnDuplicate);
+
+        given OnDuplicate ignore EditingLocal;
+        given OnDuplicate ignore Waiting;
+        given OnDuplicate ignore Failed;
+        given OnDuplicate ignore Ready;
+        given OnDuplicate ignore Paused;
+
+        // OnIdle
+        given OnIdle ignore EditingPeer;
+        given OnIdle ignore EditingLocal;
+        given OnIdle ignore Waiting;
+        given OnIdle ignore Failed;
+
+        given OnIdle(i)
+            pass Ready(ready::Message::OnIdle(i));
+
+        given OnIdle ignore Paused;
+
+        // OnPaused
+        given OnPaused ignore EditingPeer;
+        given OnPaused ignore EditingLocal;
+        given OnPaused ignore Waiting;
+        given OnPaused ignore Failed;
+        given OnPaused turn Ready into Paused;
+        given OnPaused ignore Paused;
+
         // OnPermissionDenied
         given OnPermissionDenied ignore EditingPeer;
-        given OnPermissionDenied ignore EditingLocal;
 
-        given OnPermissionDenied {}
+        given OnPermissionDenied(e)
+            turn EditingLocal(i)
+            into Failed(failed::State::new_permission_denied(i.peer_addr, i.pinned_addr(), i.fwd_addr(), i.return_mode, i.source_policy(), i.annotation, e));
+
+        given OnPermissionDenied(e)
             turn Waiting(i)
-            into Failed(failed::State::new_permission_denied(i.peer_addr, i.local_addr.pinned_addr()));
+            into Failed(failed::State::new_permission_denied(i.peer_addr, i.local_addr.pinned_addr(), i.fwd_addr, i.return_mode, i.source_policy, i.annotation, e));
 
-        given OnPermissionDenied {}
-            pass Failed(failed::Message::OnPermissionDenied);
+        given OnPermissionDenied(e)
+            pass Failed(failed::Message::OnPermissionDenied(e));
 
-        given OnPermissionDenied {}
-            turn Ready(i)
-            into Failed(failed::State::new_bind_failed(i.peer_addr, i.pinned.then_some(i.local_addr)));
+        given OnPermissionDenied(e)
+            turn Ready(i) | Paused(paused::State { ready: i })
+            into Failed(failed::State::new_permission_denied(i.peer_addr, i.pinned.then_some(i.local_addr), i.fwd_addr, i.return_mode, i.source_policy, i.annotation, e));
 
         // OnPermissionGranted
         given OnPermissionGranted ignore EditingPeer;
-        given OnPermissionGranted ignore EditingLocal;
+
+        given OnPermissionGranted {}
+            turn EditingLocal(i)
+            into Waiting(waiting::State::new(i.peer_addr, i.pinned_addr(), i.fwd_addr(), i.return_mode, i.source_policy(), i.annotation))
+            then {
+                return ::iced::Task::done(Message::OnPermissionGranted);
+            };
 
         given OnPermissionGranted {}
             pass Waiting(waiting::Message::OnPermissionGranted);
 
         given OnPermissionGranted ignore Failed;
         given OnPermissionGranted ignore Ready;
+        given OnPermissionGranted ignore Paused;
+
+        // OnRebindFailed
+        given OnRebindFailed ignore EditingPeer;
+        given OnRebindFailed ignore EditingLocal;
+        given OnRebindFailed ignore Waiting;
+        given OnRebindFailed ignore Failed;
+
+        given OnRebindFailed(e)
+            pass Ready(ready::Message::OnRebindFailed(e));
+
+        given OnRebindFailed(e)
+            pass Paused(paused::Message::OnRebindFailed(e));
+
+        // OnResumed
+        given OnResumed ignore EditingPeer;
+        given OnResumed ignore EditingLocal;
+        given OnResumed ignore Waiting;
+        given OnResumed ignore Failed;
+        given OnResumed ignore Ready;
+        given OnResumed turn Paused into Ready;
+
+        // OnRetrying
+        given OnRetrying ignore EditingPeer;
+        given OnRetrying ignore EditingLocal;
+        given OnRetrying ignore Waiting;
+
+        given OnRetrying {}
+            turn Failed(i)
+            into Waiting(waiting::State::new(i.peer_addr, i.pinned_addr, i.fwd_addr, i.return_mode, i.source_policy, i.annotation));
+
+        given OnRetrying ignore Ready;
+        given OnRetrying ignore Paused;
+
+        // OnReturnAddr
+        given OnReturnAddr ignore EditingPeer;
+        given OnReturnAddr ignore EditingLocal;
+
+        given OnReturnAddr(i)
+            pass Waiting(waiting::Message::OnReturnAddr(i));
+
+        given OnReturnAddr ignore Failed;
+
+        given OnReturnAddr(i)
+            pass Ready(ready::Message::OnReturnAddr(i));
+
+        given OnReturnAddr(i)
+            pass Paused(paused::Message::OnReturnAddr(i));
 
         // OnUnbound
         given OnUnbound ignore EditingPeer;
@@ -99,6 +233,7 @@ router_component! {
         given OnUnbound turn Waiting into EditingLocal;
         given OnUnbound turn Failed into EditingLocal;
         given OnUnbound turn Ready into EditingLocal;
+        given OnUnbound turn Paused into EditingLocal;
 
         // ToEditingLocal
         given ToEditingLocal ignore EditingPeer;
@@ -106,6 +241,7 @@ router_component! {
         given ToEditingLocal turn Waiting into EditingLocal;
         given ToEditingLocal turn Failed into EditingLocal;
         given ToEditingLocal turn Ready into EditingLocal;
+        given ToEditingLocal turn Paused into EditingLocal;
 
         // ToReady
         given ToReady ignore EditingPeer;
@@ -113,27 +249,52 @@ router_component! {
         given ToReady turn Waiting into Ready;
         given ToReady ignore Failed;
         given ToReady ignore Ready;
+        given ToReady ignore Paused;
 
         // ToWaiting
-        given ToWaiting { peer_addr, pinned_addr, }
-            turn EditingPeer(_) | EditingLocal(_)
-            into Waiting(waiting::State::new(peer_addr, pinned_addr))
-            then((command_snd, _)) {
-                command_snd
-                    .send(CommandMessage::ConnectPeer {
-                        peer_addr,
-                        local_addr: pinned_addr,
-                    })
+        given ToWaiting { peer_addr, pinned_addr, fwd_addr, return_mode, source_policy, annotation, }
+            turn EditingPeer(_) | EditingLocal(_) | Failed(_)
+            into Waiting(waiting::State::new(peer_addr, pinned_addr, fwd_addr, return_mode, source_policy.clone(), *annotation.clone()))
+            then((relay_handle, _, config)) {
+                relay_handle
+                    .connect_peer(
+                        PeerConfig::new(peer_addr)
+                            .with_local_addr(pinned_addr)
+                            .with_fwd_addr(fwd_addr)
+                            .with_return_mode(return_mode)
+                            .with_source_policy(source_policy)
+                            .with_preferred_port(config.preferred_port(peer_addr))
+                            .with_label(annotation.label())
+                            .with_idle_policy(config.idle_policy()),
+                    )
                     .unwrap();
             };
 
         given ToWaiting ignore Waiting;
-        given ToWaiting ignore Failed;
         given ToWaiting ignore Ready;
+        given ToWaiting ignore Paused;
     }
 }
 
 impl State {
+    pub fn new_waiting(
+        peer_addr: SocketAddr,
+        pinned_addr: Option<SocketAddr>,
+        fwd_addr: Option<SocketAddr>,
+        return_mode: ReturnMode,
+        source_policy: SourcePolicy,
+        annotation: Annotation,
+    ) -> Self {
+        Self::Waiting(waiting::State::new(
+            peer_addr,
+            pinned_addr,
+            fwd_addr,
+            return_mode,
+            source_policy,
+            annotation,
+        ))
+    }
+
     pub fn compare_peer(&self, other_peer_addr: SocketAddr) -> bool {
         match self {
             Self::Intermediate => {
@@ -143,11 +304,104 @@ impl State {
             Self::EditingLocal(editing_local::State { peer_addr, .. })
             | Self::Waiting(waiting::State { peer_addr, .. })
             | Self::Failed(failed::State { peer_addr, .. })
-            | Self::Ready(ready::State { peer_addr, .. }) => *peer_addr == other_peer_addr,
+            | Self::Ready(ready::State { peer_addr, .. })
+            | Self::Paused(paused::State {
+                ready: ready::State { peer_addr, .. },
+            }) => *peer_addr == other_peer_addr,
+        }
+    }
+
+    pub const fn peer_addr(&self) -> Option<SocketAddr> {
+        match self {
+            Self::Intermediate | Self::EditingPeer(_) => None,
+            Self::EditingLocal(editing_local::State { peer_addr, .. })
+            | Self::Waiting(waiting::State { peer_addr, .. })
+            | Self::Failed(failed::State { peer_addr, .. })
+            | Self::Ready(ready::State { peer_addr, .. })
+            | Self::Paused(paused::State {
+                ready: ready::State { peer_addr, .. },
+            }) => Some(*peer_addr),
+        }
+    }
+
+    pub const fn bound_addr(&self) -> Option<SocketAddr> {
+        match self {
+            Self::Waiting(i) => i.local_addr.bound_addr(),
+            Self::Ready(i) | Self::Paused(paused::State { ready: i }) => Some(i.local_addr),
+            _ => None,
         }
     }
 
     pub const fn is_uncommitted(&self) -> bool {
         matches!(self, Self::EditingPeer(..))
     }
+
+    pub const fn is_failed(&self) -> bool {
+        matches!(self, Self::Failed(..))
+    }
+
+    pub const fn is_idle(&self) -> bool {
+        matches!(self, Self::Ready(ready::State { is_idle: true, .. }))
+    }
+
+    pub fn annotation(&self) -> &Annotation {
+        match self {
+            Self::Intermediate => {
+                unreachable!("Fatal: UI state is in an intermediate state");
+            }
+            Self::EditingPeer(editing_peer::State { annotation, .. })
+            | Self::EditingLocal(editing_local::State { annotation, .. })
+            | Self::Waiting(waiting::State { annotation, .. })
+            | Self::Failed(failed::State { annotation, .. })
+            | Self::Ready(ready::State { annotation, .. })
+            | Self::Paused(paused::State {
+                ready: ready::State { annotation, .. },
+            }) => annotation,
+        }
+    }
+
+    pub fn to_saved(&self) -> Option<SavedPeer> {
+        Some(match self {
+            Self::Intermediate => {
+                unreachable!("Fatal: UI state is in an intermediate state");
+            }
+            Self::EditingPeer(_) => return None,
+            Self::EditingLocal(i) => SavedPeer {
+                peer_addr: i.peer_addr,
+                local_addr: i.pinned_addr(),
+                fwd_addr: i.fwd_addr(),
+                return_mode: i.return_mode,
+                source_policy: i.source_policy(),
+                label: i.annotation.label.clone(),
+                note: i.annotation.note.clone(),
+            },
+            Self::Waiting(i) => SavedPeer {
+                peer_addr: i.peer_addr,
+                local_addr: i.local_addr.pinned_addr(),
+                fwd_addr: i.fwd_addr,
+                return_mode: i.return_mode,
+                source_policy: i.source_policy.clone(),
+                label: i.annotation.label.clone(),
+                note: i.annotation.note.clone(),
+            },
+            Self::Failed(i) => SavedPeer {
+                peer_addr: i.peer_addr,
+                local_addr: i.pinned_addr,
+                fwd_addr: i.fwd_addr,
+                return_mode: i.return_mode,
+                source_policy: i.source_policy.clone(),
+                label: i.annotation.label.clone(),
+                note: i.annotation.note.clone(),
+            },
+            Self::Ready(i) | Self::Paused(paused::State { ready: i }) => SavedPeer {
+                peer_addr: i.peer_addr,
+                local_addr: i.pinned.then_some(i.local_addr),
+                fwd_addr: i.fwd_addr,
+                return_mode: i.return_mode,
+                source_policy: i.source_policy.clone(),
+                label: i.annotation.label.clone(),
+                note: i.annotation.note.clone(),
+            },
+        })
+    }
 }
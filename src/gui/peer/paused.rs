@@ -0,0 +1,96 @@
+use std::net::SocketAddr;
+
+use iced::{
+    widget::{button, horizontal_space, row, text, text_input},
+    Element, Length, Task,
+};
+
+use turn_relay_worker::{PeerError, RelayHandle};
+
+use crate::config::Config;
+use crate::gui::{peer::ready, types::IcedComponent};
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    UpdateLabel(String),
+    Resume,
+    Delete,
+    OnBound(SocketAddr),
+    OnRebindFailed(PeerError),
+    OnReturnAddr(SocketAddr),
+}
+
+/// A ready peer whose traffic is not relayed, until it is resumed.
+#[derive(Debug, Clone)]
+pub struct State {
+    pub ready: ready::State,
+}
+
+impl From<ready::State> for State {
+    fn from(value: ready::State) -> Self {
+        Self { ready: value }
+    }
+}
+
+impl IcedComponent for State {
+    type Message = Message;
+    type TaskMessage = super::Message;
+    type ExtraUpdateArgs<'a> = (&'a RelayHandle, SocketAddr, &'a Config);
+    type ExtraViewArgs<'a> = usize;
+    type ExtraSubscriptionArgs<'a> = ();
+
+    fn update(
+        &mut self,
+        message: Self::Message,
+        extra: Self::ExtraUpdateArgs<'_>,
+    ) -> Task<Self::TaskMessage> {
+        let (relay_handle, _relay_addr, _config) = extra;
+
+        match message {
+            Message::UpdateLabel(i) => {
+                self.ready.annotation.label = i;
+            }
+
+            Message::Resume => {
+                relay_handle.resume_peer(self.ready.peer_addr).unwrap();
+            }
+
+            Message::Delete => {
+                relay_handle.disconnect_peer(self.ready.peer_addr).unwrap();
+            }
+
+            Message::OnBound(i) => {
+                return self.ready.update(ready::Message::OnBound(i), extra);
+            }
+
+            Message::OnRebindFailed(e) => {
+                return self.ready.update(ready::Message::OnRebindFailed(e), extra);
+            }
+
+            Message::OnReturnAddr(i) => {
+                return self.ready.update(ready::Message::OnReturnAddr(i), extra);
+            }
+        }
+
+        Task::none()
+    }
+
+    fn view<'a>(&'a self, index: Self::ExtraViewArgs<'_>) -> Element<'a, Self::Message> {
+        row![
+            text!("{})", index + 1).width(48),
+            horizontal_space().width(8),
+            self.ready.annotation.view(Message::UpdateLabel),
+            horizontal_space().width(8),
+            text_input("", format!("{}", self.ready.peer_addr).as_ref()),
+            horizontal_space().width(8),
+            text_input("", format!("{}", self.ready.local_addr).as_ref()),
+            horizontal_space().width(8),
+            text!("Paused").width(Length::Fill).style(text::secondary),
+            horizontal_space().width(8),
+            button(text!("Resume")).on_press(Message::Resume),
+            horizontal_space().width(8),
+            button(text!("X")).on_press(Message::Delete),
+        ]
+        .into()
+    }
+}
@@ -1,36 +1,84 @@
+use std::mem::take;
 use std::net::SocketAddr;
 
 use iced::{
-    widget::{button, horizontal_space, row, text, text_input},
+    widget::{button, container, horizontal_space, row, text, text_input, tooltip},
     Element, Task,
 };
-use tokio::sync::broadcast;
 
-use crate::{
-    gui::{peer::waiting, types::IcedComponent},
-    worker::CommandMessage,
+use turn_relay_worker::{PeerError, RelayHandle, ReturnMode, SourcePolicy, LOCAL_IP};
+
+use crate::config::Config;
+use crate::gui::{
+    addr_input::AddrInput,
+    peer::{
+        paused,
+        types::{format_addr, Annotation},
+        waiting,
+    },
+    types::IcedComponent,
 };
 
 #[derive(Debug, Clone)]
 pub enum Message {
+    UpdateLabel(String),
+    UpdateLocal(String),
+    Rebind,
+    Pause,
     Delete,
     OnBound(SocketAddr),
+    OnRebindFailed(PeerError),
+    OnReturnAddr(SocketAddr),
+    OnDropped(u64),
+    OnIdle(bool),
 }
 
 #[derive(Debug, Clone)]
 pub struct State {
     pub peer_addr: SocketAddr,
     pub local_addr: SocketAddr,
+    local_input: AddrInput,
     pub pinned: bool,
+    is_rebinding: bool,
+    rebind_error: Option<PeerError>,
+    pub fwd_addr: Option<SocketAddr>,
+    pub return_mode: ReturnMode,
+    pub return_addr: Option<SocketAddr>,
+    pub source_policy: SourcePolicy,
+    pub drop_count: u64,
+    pub is_idle: bool,
+    pub annotation: Annotation,
 }
 
 #[allow(clippy::fallible_impl_from)]
 impl From<waiting::State> for State {
     fn from(value: waiting::State) -> Self {
+        let local_addr = value.local_addr.bound_addr().unwrap();
+
         Self {
             peer_addr: value.peer_addr,
-            local_addr: value.local_addr.bound_addr().unwrap(),
+            local_addr,
+            local_input: AddrInput::parse(local_addr.to_string(), LOCAL_IP),
             pinned: value.local_addr.is_pinned(),
+            is_rebinding: false,
+            rebind_error: None,
+            fwd_addr: value.fwd_addr,
+            return_mode: value.return_mode,
+            return_addr: value.return_addr,
+            source_policy: value.source_policy,
+            drop_count: value.drop_count,
+            is_idle: false,
+            annotation: value.annotation,
+        }
+    }
+}
+
+impl From<paused::State> for State {
+    fn from(value: paused::State) -> Self {
+        // The idle time starts over once resumed.
+        Self {
+            is_idle: false,
+            ..value.ready
         }
     }
 }
@@ -38,25 +86,71 @@ impl From<waiting::State> for State {
 impl IcedComponent for State {
     type Message = Message;
     type TaskMessage = super::Message;
-    type ExtraUpdateArgs<'a> = (&'a broadcast::Sender<CommandMessage>, SocketAddr);
+    type ExtraUpdateArgs<'a> = (&'a RelayHandle, SocketAddr, &'a Config);
     type ExtraViewArgs<'a> = usize;
     type ExtraSubscriptionArgs<'a> = ();
 
     fn update(
         &mut self,
         message: Self::Message,
-        (command_snd, _relay_addr): Self::ExtraUpdateArgs<'_>,
+        (relay_handle, _relay_addr, _config): Self::ExtraUpdateArgs<'_>,
     ) -> Task<Self::TaskMessage> {
         match message {
+            Message::UpdateLabel(i) => {
+                self.annotation.label = i;
+            }
+
+            Message::UpdateLocal(i) => {
+                self.local_input = AddrInput::parse(i, LOCAL_IP);
+                self.rebind_error = None;
+            }
+
+            Message::Rebind => {
+                if let Some(i) = self.local_input.addr().filter(|i| *i != self.local_addr) {
+                    self.is_rebinding = true;
+                    self.rebind_error = None;
+                    relay_handle.rebind_peer(self.peer_addr, i).unwrap();
+                }
+            }
+
+            Message::Pause => {
+                relay_handle.pause_peer(self.peer_addr).unwrap();
+            }
+
             Message::Delete => {
-                command_snd
-                    .send(CommandMessage::DisconnectPeer(self.peer_addr))
-                    .unwrap();
+                relay_handle.disconnect_peer(self.peer_addr).unwrap();
             }
 
             Message::OnBound(i) => {
-                self.pinned &= self.local_addr == i;
+                // A peer that moved to the address it was rebound on is pinned
+                // there.
+                if take(&mut self.is_rebinding) {
+                    self.pinned |= self.local_addr != i;
+                } else {
+                    self.pinned &= self.local_addr == i;
+                }
+
                 self.local_addr = i;
+                self.local_input = AddrInput::parse(i.to_string(), LOCAL_IP);
+            }
+
+            Message::OnRebindFailed(e) => {
+                // The socket stays where it was.
+                self.is_rebinding = false;
+                self.local_input = AddrInput::parse(self.local_addr.to_string(), LOCAL_IP);
+                self.rebind_error = Some(e);
+            }
+
+            Message::OnReturnAddr(i) => {
+                self.return_addr = Some(i);
+            }
+
+            Message::OnDropped(i) => {
+                self.drop_count = i;
+            }
+
+            Message::OnIdle(i) => {
+                self.is_idle = i;
             }
         }
 
@@ -67,12 +161,61 @@ impl IcedComponent for State {
         row![
             text!("{})", index + 1).width(48),
             horizontal_space().width(8),
+            self.annotation.view(Message::UpdateLabel),
+            horizontal_space().width(8),
             text_input("", format!("{}", self.peer_addr).as_ref()),
             horizontal_space().width(8),
-            text_input("", format!("{}", self.local_addr).as_ref()),
+            self.local_input
+                .view("127.0.0.1:12345", Message::UpdateLocal, Message::Rebind),
+            horizontal_space().width(8),
+            if self.return_mode == ReturnMode::Forward {
+                text_input("Default forward", &format_addr(self.fwd_addr))
+            } else {
+                text_input("Learning...", &format_addr(self.return_addr))
+            },
             horizontal_space().width(8),
-            button(text!("X")).on_press(Message::Delete),
         ]
+        .push_maybe(self.rebind_error.as_ref().map(|e| {
+            row![
+                tooltip(
+                    text!("{e}").style(text::danger),
+                    container(text!("{}", e.hint()))
+                        .padding(4)
+                        .style(container::rounded_box),
+                    tooltip::Position::Bottom,
+                ),
+                horizontal_space().width(8),
+            ]
+        }))
+        .push_maybe(
+            (self.source_policy != SourcePolicy::Any || self.drop_count > 0).then(|| {
+                row![
+                    tooltip(
+                        text!("{} dropped", self.drop_count),
+                        container(text!("Allowed sources: {}", self.source_policy))
+                            .padding(4)
+                            .style(container::rounded_box),
+                        tooltip::Position::Bottom,
+                    ),
+                    horizontal_space().width(8),
+                ]
+            }),
+        )
+        .push_maybe(self.is_idle.then(|| {
+            row![
+                tooltip(
+                    text!("Idle").style(text::secondary),
+                    container(text!("No traffic for a while"))
+                        .padding(4)
+                        .style(container::rounded_box),
+                    tooltip::Position::Bottom,
+                ),
+                horizontal_space().width(8),
+            ]
+        }))
+        .push(button(text!("Pause")).on_press(Message::Pause))
+        .push(horizontal_space().width(8))
+        .push(button(text!("X")).on_press(Message::Delete))
         .into()
     }
 }
@@ -1,5 +1,111 @@
 use std::{fmt::Display, net::SocketAddr};
 
+use iced::{
+    widget::{column, container, text, text_input, tooltip},
+    Element,
+};
+use turn_relay_worker::SourcePolicy;
+
+pub fn format_addr(addr: Option<SocketAddr>) -> String {
+    addr.map_or_else(String::new, |i| format!("{i}"))
+}
+
+pub fn format_source_policy(policy: &SourcePolicy) -> String {
+    if *policy == SourcePolicy::Any {
+        String::new()
+    } else {
+        policy.to_string()
+    }
+}
+
+/// Text input of the allowed sources, parsed as the user types, which shows
+/// the error under the field. An invalid input is never taken as any source.
+#[derive(Debug, Clone)]
+pub struct SourcePolicyInput {
+    value: String,
+    parsed: Result<SourcePolicy, String>,
+}
+
+impl Default for SourcePolicyInput {
+    fn default() -> Self {
+        Self::parse(String::new())
+    }
+}
+
+impl SourcePolicyInput {
+    pub fn parse(value: String) -> Self {
+        let parsed = value.parse().map_err(|e| format!("{e}"));
+        Self { value, parsed }
+    }
+
+    pub fn from_policy(policy: &SourcePolicy) -> Self {
+        Self::parse(format_source_policy(policy))
+    }
+
+    pub const fn is_valid(&self) -> bool {
+        self.parsed.is_ok()
+    }
+
+    pub fn policy(&self) -> Option<SourcePolicy> {
+        self.parsed.as_ref().ok().cloned()
+    }
+
+    pub fn view<'a, M: Clone + 'a>(
+        &'a self,
+        on_input: impl Fn(String) -> M + 'a,
+        on_submit: M,
+    ) -> Element<'a, M> {
+        column![
+            text_input("Any source", &self.value)
+                .on_input(on_input)
+                .on_submit(on_submit),
+            match &self.parsed {
+                Ok(_) => text!(""),
+                Err(e) => text!("Invalid network: {e}").style(text::danger),
+            }
+            .size(12),
+        ]
+        .into()
+    }
+}
+
+/// Name and note that the user gave to a peer.
+#[derive(Debug, Clone, Default)]
+pub struct Annotation {
+    pub label: String,
+    pub note: String,
+}
+
+impl Annotation {
+    pub fn label(&self) -> Option<String> {
+        let label = self.label.trim();
+        (!label.is_empty()).then(|| label.to_string())
+    }
+
+    /// Shows the label as an input, and the note as its tooltip.
+    pub fn view<'a, M: Clone + 'a>(
+        &'a self,
+        on_input: impl Fn(String) -> M + 'a,
+    ) -> Element<'a, M> {
+        let label = text_input("Name", &self.label)
+            .on_input(on_input)
+            .width(120);
+
+        if self.note.is_empty() {
+            label.into()
+        } else {
+            tooltip(
+                label,
+                container(text!("{}", self.note))
+                    .padding(4)
+                    .style(container::rounded_box),
+                tooltip::Position::Bottom,
+            )
+            .into()
+        }
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct SocketState {
     bound_addr: Option<SocketAddr>,
@@ -57,25 +163,25 @@ impl SocketState {
     }
 
     #[inline]
-    pub fn pin(&mut self, maybe_addr: Option<SocketAddr>) {
+    pub const fn pin(&mut self, maybe_addr: Option<SocketAddr>) {
         self.pinned_addr = maybe_addr;
     }
 
     #[must_use]
     #[inline]
-    pub fn with_pin(mut self, addr: Option<SocketAddr>) -> Self {
+    pub const fn with_pin(mut self, addr: Option<SocketAddr>) -> Self {
         self.pin(addr);
         self
     }
 
     #[inline]
-    pub fn bind(&mut self, maybe_addr: Option<SocketAddr>) {
+    pub const fn bind(&mut self, maybe_addr: Option<SocketAddr>) {
         self.bound_addr = maybe_addr;
     }
 
     #[must_use]
     #[inline]
-    pub fn with_bind(mut self, addr: Option<SocketAddr>) -> Self {
+    pub const fn with_bind(mut self, addr: Option<SocketAddr>) -> Self {
         self.bind(addr);
         self
     }
@@ -4,33 +4,57 @@ use iced::{
     widget::{button, horizontal_space, row, text, text_input},
     Element, Length, Task,
 };
-use tokio::sync::broadcast;
 
-use crate::{
-    gui::{peer::types::SocketState, types::IcedComponent},
-    worker::CommandMessage,
+use turn_relay_worker::{RelayHandle, ReturnMode, SourcePolicy};
+
+use crate::config::Config;
+use crate::gui::{
+    peer::types::{Annotation, SocketState},
+    types::IcedComponent,
 };
 
 #[derive(Debug, Clone)]
 pub enum Message {
+    UpdateLabel(String),
     Delete,
     OnPermissionGranted,
     OnBound(SocketAddr),
+    OnReturnAddr(SocketAddr),
+    OnDropped(u64),
 }
 
 #[derive(Debug, Clone)]
 pub struct State {
     pub peer_addr: SocketAddr,
     pub local_addr: SocketState,
+    pub fwd_addr: Option<SocketAddr>,
+    pub return_mode: ReturnMode,
+    pub return_addr: Option<SocketAddr>,
+    pub source_policy: SourcePolicy,
+    pub drop_count: u64,
     pub authorized: bool,
+    pub annotation: Annotation,
 }
 
 impl State {
-    pub fn new(peer_addr: SocketAddr, pinned_addr: Option<SocketAddr>) -> Self {
+    pub fn new(
+        peer_addr: SocketAddr,
+        pinned_addr: Option<SocketAddr>,
+        fwd_addr: Option<SocketAddr>,
+        return_mode: ReturnMode,
+        source_policy: SourcePolicy,
+        annotation: Annotation,
+    ) -> Self {
         Self {
             peer_addr,
             local_addr: SocketState::default().with_pin(pinned_addr),
+            fwd_addr,
+            return_mode,
+            return_addr: None,
+            source_policy,
+            drop_count: 0,
             authorized: false,
+            annotation,
         }
     }
 }
@@ -38,20 +62,22 @@ impl State {
 impl IcedComponent for State {
     type Message = Message;
     type TaskMessage = super::Message;
-    type ExtraUpdateArgs<'a> = (&'a broadcast::Sender<CommandMessage>, SocketAddr);
+    type ExtraUpdateArgs<'a> = (&'a RelayHandle, SocketAddr, &'a Config);
     type ExtraViewArgs<'a> = usize;
     type ExtraSubscriptionArgs<'a> = ();
 
     fn update(
         &mut self,
         message: Self::Message,
-        (command_snd, _relay_addr): Self::ExtraUpdateArgs<'_>,
+        (relay_handle, _relay_addr, _config): Self::ExtraUpdateArgs<'_>,
     ) -> Task<Self::TaskMessage> {
         match message {
+            Message::UpdateLabel(i) => {
+                self.annotation.label = i;
+            }
+
             Message::Delete => {
-                command_snd
-                    .send(CommandMessage::DisconnectPeer(self.peer_addr))
-                    .unwrap();
+                relay_handle.disconnect_peer(self.peer_addr).unwrap();
             }
 
             Message::OnPermissionGranted => {
@@ -69,6 +95,14 @@ impl IcedComponent for State {
                     return Task::done(super::Message::ToReady);
                 }
             }
+
+            Message::OnReturnAddr(i) => {
+                self.return_addr = Some(i);
+            }
+
+            Message::OnDropped(i) => {
+                self.drop_count = i;
+            }
         }
 
         Task::none()
@@ -78,6 +112,8 @@ impl IcedComponent for State {
         row![
             text!("{})", index + 1).width(48),
             horizontal_space().width(8),
+            self.annotation.view(Message::UpdateLabel),
+            horizontal_space().width(8),
             text_input("", format!("{}", self.peer_addr).as_ref()),
             horizontal_space().width(8),
             if !self.authorized {
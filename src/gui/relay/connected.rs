@@ -1,122 +1,845 @@
-use std::net::SocketAddr;
+use std::fmt::Display;
+use std::net::{IpAddr, SocketAddr};
 
 use iced::widget::{
-    button, column, horizontal_space, row, scrollable, text, text_input, vertical_space,
+    button, checkbox, column, horizontal_space, pick_list, row, scrollable, text, text_input,
+    vertical_space,
 };
 use iced::{clipboard, Element, Length, Task};
-use tokio::sync::broadcast;
 
-use crate::gui::peer;
+use turn_relay_worker::{
+    addr, PortRange, RelayHandle, ReturnMode, SourcePolicy, TrafficStats, ALL_IP, LOCAL_IP,
+};
+
+use crate::config::{Config, SavedPeer};
+use crate::gui::addr_input::AddrInput;
+use crate::gui::peer::{self, types::Annotation};
+use crate::gui::relay::traffic::{History, Traffic};
+use crate::gui::relay::{import, list, qr};
+use crate::gui::storage::Storage;
 use crate::gui::types::IcedComponent;
-use crate::macros::addr;
-use crate::worker::CommandMessage;
-use crate::LOCAL_IP;
+use crate::invite::Invite;
 
 #[derive(Debug, Clone)]
 pub enum Message {
     CopyRelayAddr,
+    CopyInvite,
+    ToggleQr,
+    ForQr(qr::Message),
     Disconnect,
     UpdateFwdAddr(String),
     ChangeFwdAddr,
+    OnFwdAddrChanged(SocketAddr),
+    OnFwdAddrResolved(String, SocketAddr),
+    OnFwdResolveFailed(String, String),
+    ChangeBindIp(BindChoice),
+    OnBindIpChanged(IpAddr),
+    UpdatePortRange(String),
+    ChangePortRange,
+    OnPortRangeChanged(Option<PortRange>),
+    ToggleStablePorts(bool),
+    UpdateIdleAfter(String),
+    UpdateRemoveIdleAfter(String),
+    ChangeIdle,
     AddPeer,
+    ToggleImport,
+    ForImport(import::Message),
+    ImportPeers(Vec<SavedPeer>),
+    PasteInvite,
+    OnInvitePasted(Option<String>),
+    ReloadPeers,
+    SavePeers,
+    ForList(list::Message),
     ForPeerByIndex(usize, peer::Message),
     ForPeerByAddr(SocketAddr, peer::Message),
+    OnPeerTraffic(SocketAddr, TrafficStats),
+    OnTrafficTick,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BindChoice {
+    name: String,
+    ip: IpAddr,
+}
+
+impl Display for BindChoice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.name, self.ip)
+    }
+}
+
+impl BindChoice {
+    fn list() -> Vec<Self> {
+        let mut choices = vec![Self {
+            name: "All interfaces".into(),
+            ip: ALL_IP,
+        }];
+
+        match if_addrs::get_if_addrs() {
+            Ok(i) => choices.extend(i.into_iter().filter(|i| i.ip().is_ipv4()).map(|i| Self {
+                ip: i.ip(),
+                name: i.name,
+            })),
+            Err(e) => {
+                eprintln!("Could not list the network interfaces: {e}");
+
+                choices.push(Self {
+                    name: "Loopback".into(),
+                    ip: LOCAL_IP,
+                });
+            }
+        }
+
+        choices
+    }
+}
+
+/// Port range being edited.
+#[derive(Debug, Clone)]
+struct PortRangeEdit {
+    value: String,
+    error: Option<String>,
+}
+
+/// Idle times being edited, in seconds.
+#[derive(Debug, Clone)]
+struct IdleEdit {
+    idle_after: String,
+    remove_after: String,
+    error: Option<String>,
 }
 
 #[derive(Debug, Clone)]
 pub struct State {
     pub server: String,
+    pub profile: Option<String>,
     relay_addr: SocketAddr,
-    fwd_addr: String,
+    fwd_addr: AddrInput,
+    fwd_status: Option<Result<SocketAddr, String>>,
+    bind_choices: Vec<BindChoice>,
+    bind_ip: Option<IpAddr>,
+    port_range: Option<Box<PortRangeEdit>>,
+    idle: Option<Box<IdleEdit>>,
+    import: Option<Box<import::State>>,
+    qr: Option<Box<qr::State>>,
+    invite_error: Option<String>,
+    save_error: Option<String>,
+    list: list::State,
     peers: Vec<peer::State>,
+    traffic: Box<Traffic>,
 }
 
 impl State {
-    pub const fn new(server: String, relay_addr: SocketAddr) -> Self {
+    pub fn new(
+        server: String,
+        profile: Option<String>,
+        fwd_addr: Option<String>,
+        relay_addr: SocketAddr,
+    ) -> Self {
         Self {
             server,
+            profile,
             relay_addr,
-            fwd_addr: String::new(),
+            fwd_addr: AddrInput::parse_target(fwd_addr.unwrap_or_default()),
+            fwd_status: None,
+            bind_choices: BindChoice::list(),
+            bind_ip: None,
+            port_range: None,
+            idle: None,
+            import: None,
+            qr: None,
+            invite_error: None,
+            save_error: None,
+            list: list::State::default(),
             peers: vec![],
+            traffic: Box::default(),
+        }
+    }
+
+    fn connect_saved_peers(
+        &mut self,
+        relay_handle: &RelayHandle,
+        config: &Config,
+        saved_peers: Vec<SavedPeer>,
+    ) -> Task<super::Message> {
+        let mut tasks = vec![];
+
+        for SavedPeer {
+            peer_addr,
+            local_addr,
+            fwd_addr,
+            return_mode,
+            source_policy,
+            label,
+            note,
+        } in saved_peers
+        {
+            if self.peers.iter().any(|i| i.compare_peer(peer_addr)) {
+                continue;
+            }
+
+            let index = self.peers.len();
+            let mut peer = peer::State::default();
+
+            tasks.push(
+                peer.update(
+                    peer::Message::ToWaiting {
+                        peer_addr,
+                        pinned_addr: local_addr,
+                        fwd_addr,
+                        return_mode,
+                        source_policy,
+                        annotation: Box::new(Annotation { label, note }),
+                    },
+                    (relay_handle, self.relay_addr, config),
+                )
+                .map(move |i| super::Message::ForPeerByIndex(index, i)),
+            );
+
+            self.peers.push(peer);
         }
+
+        Task::batch(tasks)
+    }
+
+    fn reload_peers(
+        &mut self,
+        relay_handle: &RelayHandle,
+        storage: &Storage,
+    ) -> Task<super::Message> {
+        let Some(profile) = self
+            .profile
+            .as_ref()
+            .and_then(|i| storage.config.profile(i))
+        else {
+            return Task::none();
+        };
+
+        let saved_peers = profile.peers.clone();
+        self.connect_saved_peers(relay_handle, &storage.config, saved_peers)
+    }
+
+    /// Saves the configuration, and shows the error if it fails.
+    fn save_config(&mut self, storage: &Storage) {
+        self.save_error = storage.save_config().err().map(|e| format!("{e:#}"));
+    }
+
+    fn save_peers(&mut self, storage: &mut Storage) {
+        let Some(profile) = self
+            .profile
+            .as_ref()
+            .and_then(|i| storage.config.profile_mut(i))
+        else {
+            return;
+        };
+
+        profile.peers = self
+            .peers
+            .iter()
+            .filter_map(peer::State::to_saved)
+            .collect();
+        self.save_config(storage);
+    }
+
+    fn change_fwd_addr(&mut self, relay_handle: &RelayHandle, storage: &mut Storage) {
+        let Some(target) = self.fwd_addr.target() else {
+            return;
+        };
+
+        relay_handle.change_fwd_target(target.clone()).unwrap();
+
+        storage.config.fwd_addr = Some(target);
+        self.save_config(storage);
+    }
+
+    fn change_port_range(&mut self, relay_handle: &RelayHandle, storage: &mut Storage) {
+        let Some(port_range) = self.port_range.take() else {
+            return;
+        };
+
+        let port_range = match port_range.value.trim() {
+            "" => None,
+            i => match i.parse() {
+                Ok(i) => Some(i),
+                Err(e) => {
+                    self.port_range = Some(Box::new(PortRangeEdit {
+                        error: Some(format!("Invalid port range: {e}")),
+                        ..*port_range
+                    }));
+                    return;
+                }
+            },
+        };
+
+        relay_handle.change_port_range(port_range).unwrap();
+
+        storage.config.port_range = port_range;
+        self.save_config(storage);
+    }
+
+    fn change_idle(&mut self, storage: &mut Storage) {
+        let Some(idle) = self.idle.take() else {
+            return;
+        };
+
+        let parse = |i: &str| match i.trim() {
+            "" => Ok(None),
+            i => i.parse().map(Some).map_err(|e| format!("{i}: {e}")),
+        };
+
+        match (parse(&idle.idle_after), parse(&idle.remove_after)) {
+            (Ok(idle_after), Ok(remove_after)) => {
+                storage.config.idle_after = idle_after;
+                storage.config.remove_idle_after = remove_after;
+                self.save_config(storage);
+            }
+            (Err(e), _) | (_, Err(e)) => {
+                self.idle = Some(Box::new(IdleEdit {
+                    error: Some(format!("Invalid number of seconds {e}")),
+                    ..*idle
+                }));
+            }
+        }
+    }
+
+    fn edit_idle(&mut self, config: &Config) -> &mut IdleEdit {
+        self.idle.get_or_insert_with(|| {
+            let format = |i: Option<u64>| i.map(|i| i.to_string()).unwrap_or_default();
+
+            Box::new(IdleEdit {
+                idle_after: format(config.idle_after),
+                remove_after: format(config.remove_idle_after),
+                error: None,
+            })
+        })
+    }
+
+    fn invite(&self, storage: &Storage) -> String {
+        let invite = Invite {
+            relay_addr: self.relay_addr,
+            label: storage.config.invite_label.clone(),
+            fwd_port: None,
+        };
+
+        invite.encode(storage.invite_secret())
+    }
+
+    fn toggle_qr(&mut self, storage: &Storage) {
+        self.qr = match self.qr {
+            Some(_) => None,
+            None => Some(Box::new(qr::State::new(
+                self.relay_addr,
+                self.invite(storage),
+            ))),
+        };
+    }
+
+    fn paste_invite(
+        &mut self,
+        relay_handle: &RelayHandle,
+        storage: &Storage,
+        code: Option<String>,
+    ) -> Task<super::Message> {
+        let invite = code
+            .ok_or_else(|| "Clipboard is empty, or does not contain text".to_string())
+            .and_then(|i| Invite::decode(&i, storage.invite_secret()).map_err(|e| format!("{e:#}")))
+            .and_then(|i| {
+                if self
+                    .peers
+                    .iter()
+                    .any(|peer| peer.compare_peer(i.relay_addr))
+                {
+                    Err(format!("Peer {} is already added", i.relay_addr))
+                } else {
+                    Ok(i)
+                }
+            });
+
+        match invite {
+            Ok(invite) => {
+                self.invite_error = None;
+
+                let saved_peer = SavedPeer {
+                    peer_addr: invite.relay_addr,
+                    local_addr: None,
+                    fwd_addr: invite.fwd_port.map(|i| addr!(LOCAL_IP:i)),
+                    return_mode: ReturnMode::default(),
+                    source_policy: SourcePolicy::default(),
+                    label: invite.label.unwrap_or_default(),
+                    note: String::new(),
+                };
+
+                self.connect_saved_peers(relay_handle, &storage.config, vec![saved_peer])
+            }
+            Err(e) => {
+                self.invite_error = Some(e);
+                Task::none()
+            }
+        }
+    }
+
+    fn update_peer(
+        &mut self,
+        index: usize,
+        message: peer::Message,
+        relay_handle: &RelayHandle,
+        config: &Config,
+    ) -> Task<super::Message> {
+        // Peers are told apart by their address, so that it cannot be added
+        // twice.
+        let message = match message {
+            peer::Message::ToWaiting { peer_addr, .. }
+                if self
+                    .peers
+                    .iter()
+                    .enumerate()
+                    .any(|(i, peer)| i != index && peer.compare_peer(peer_addr)) =>
+            {
+                peer::Message::OnDuplicate
+            }
+            i => i,
+        };
+
+        self.peers[index]
+            .update(message, (relay_handle, self.relay_addr, config))
+            .map(move |i| super::Message::ForPeerByIndex(index, i))
+    }
+
+    fn find_or_add_peer(
+        &mut self,
+        peer_addr: SocketAddr,
+        message: &peer::Message,
+    ) -> Option<usize> {
+        if let Some(index) = self.peers.iter().position(|i| i.compare_peer(peer_addr)) {
+            return Some(index);
+        }
+
+        if matches!(message, peer::Message::OnUnbound) {
+            eprintln!("non-existent peer {peer_addr} ignored: {message:?} @ {self:?}");
+            return None;
+        }
+
+        // The peer was connected from outside of the GUI, e.g. by the control
+        // socket.
+        self.peers.push(peer::State::new_waiting(
+            peer_addr,
+            None,
+            None,
+            ReturnMode::default(),
+            SourcePolicy::default(),
+            Annotation::default(),
+        ));
+
+        Some(self.peers.len() - 1)
+    }
+
+    fn remember_port(
+        &mut self,
+        index: usize,
+        peer_addr: SocketAddr,
+        port: u16,
+        storage: &mut Storage,
+    ) {
+        let is_pinned = self.peers[index]
+            .to_saved()
+            .is_some_and(|i| i.local_addr.is_some());
+
+        if !is_pinned && storage.config.remember_port(peer_addr, port) {
+            self.save_config(storage);
+        }
+    }
+
+    /// Handles the messages of the forward, bind, port and idle settings.
+    fn update_settings(
+        &mut self,
+        message: Message,
+        relay_handle: &RelayHandle,
+        storage: &mut Storage,
+    ) {
+        match message {
+            Message::UpdateFwdAddr(i) => {
+                self.fwd_addr = AddrInput::parse_target(i);
+            }
+
+            Message::ChangeFwdAddr => {
+                self.change_fwd_addr(relay_handle, storage);
+            }
+
+            Message::OnFwdAddrChanged(i) => {
+                self.fwd_addr = AddrInput::parse_target(format!("{i}"));
+                self.fwd_status = None;
+            }
+
+            Message::OnFwdAddrResolved(target, i) => {
+                self.fwd_addr = AddrInput::parse_target(target);
+                self.fwd_status = Some(Ok(i));
+            }
+
+            Message::OnFwdResolveFailed(target, e) => {
+                self.fwd_addr = AddrInput::parse_target(target);
+                self.fwd_status = Some(Err(e));
+            }
+
+            Message::ChangeBindIp(i) => {
+                relay_handle.change_bind_ip(i.ip).unwrap();
+
+                storage.config.bind_ip = Some(i.ip);
+                self.save_config(storage);
+            }
+
+            Message::OnBindIpChanged(i) => {
+                self.bind_ip = Some(i);
+            }
+
+            Message::UpdatePortRange(i) => {
+                self.port_range = Some(Box::new(PortRangeEdit {
+                    value: i,
+                    error: None,
+                }));
+            }
+
+            Message::ChangePortRange => {
+                self.change_port_range(relay_handle, storage);
+            }
+
+            // It was changed elsewhere, e.g. by the control socket.
+            Message::OnPortRangeChanged(i) if i != storage.config.port_range => {
+                self.port_range = Some(Box::new(PortRangeEdit {
+                    value: i.map(|i| i.to_string()).unwrap_or_default(),
+                    error: None,
+                }));
+            }
+
+            Message::ToggleStablePorts(i) => {
+                storage.config.stable_ports = i;
+                self.save_config(storage);
+            }
+
+            Message::UpdateIdleAfter(i) => {
+                let idle = self.edit_idle(&storage.config);
+                idle.idle_after = i;
+                idle.error = None;
+            }
+
+            Message::UpdateRemoveIdleAfter(i) => {
+                let idle = self.edit_idle(&storage.config);
+                idle.remove_after = i;
+                idle.error = None;
+            }
+
+            Message::ChangeIdle => {
+                self.change_idle(storage);
+            }
+
+            _ => {}
+        }
+    }
+
+    fn view_bind<'a>(&'a self, storage: &Storage) -> Element<'a, Message> {
+        let bind_ip = self.bind_ip.or(storage.config.bind_ip).unwrap_or(LOCAL_IP);
+
+        let exposed_count = self
+            .peers
+            .iter()
+            .filter_map(peer::State::bound_addr)
+            .filter(|i| !i.ip().is_loopback())
+            .count();
+
+        column![
+            row![
+                text!("Bind peers on").width(96),
+                horizontal_space().width(8),
+                pick_list(
+                    self.bind_choices.as_slice(),
+                    self.bind_choices.iter().find(|i| i.ip == bind_ip).cloned(),
+                    Message::ChangeBindIp
+                )
+                .placeholder(bind_ip.to_string())
+                .width(Length::Fill),
+            ],
+            row![
+                horizontal_space().width(96 + 8),
+                match exposed_count {
+                    0 => text!(""),
+                    1 => text!("Warning: A peer socket is reachable from other machines."),
+                    i => text!("Warning: {i} peer sockets are reachable from other machines."),
+                }
+                .style(text::danger),
+            ],
+        ]
+        .into()
+    }
+
+    fn view_idle<'a>(&'a self, storage: &Storage) -> Element<'a, Message> {
+        let format = |i: Option<u64>| i.map(|i| i.to_string()).unwrap_or_default();
+        let (idle_after, remove_after) = self.idle.as_ref().map_or_else(
+            || {
+                (
+                    format(storage.config.idle_after),
+                    format(storage.config.remove_idle_after),
+                )
+            },
+            |i| (i.idle_after.clone(), i.remove_after.clone()),
+        );
+
+        let error = self.idle.as_ref().and_then(|i| i.error.as_ref());
+
+        column![row![
+            text!("Idle after").width(96),
+            horizontal_space().width(8),
+            text_input("Never", &idle_after)
+                .on_input(Message::UpdateIdleAfter)
+                .on_submit(Message::ChangeIdle),
+            horizontal_space().width(8),
+            text!("Remove after"),
+            horizontal_space().width(8),
+            text_input("Never", &remove_after)
+                .on_input(Message::UpdateRemoveIdleAfter)
+                .on_submit(Message::ChangeIdle),
+            horizontal_space().width(8),
+            text!("seconds"),
+            horizontal_space().width(8),
+            button(text!("Apply")).on_press(Message::ChangeIdle),
+        ]]
+        .push_maybe(error.map(|e| {
+            row![
+                horizontal_space().width(96 + 8),
+                text!("{e}").style(text::danger),
+            ]
+        }))
+        .into()
+    }
+
+    /// Shows a peer row, with a sparkline of its traffic. Its messages are
+    /// sent by address, unless it has none yet.
+    fn view_peer<'a>(
+        &'a self,
+        position: usize,
+        index: usize,
+        peer: &'a peer::State,
+    ) -> Element<'a, Message> {
+        let peer_addr = peer.peer_addr();
+
+        column![
+            vertical_space().height(if position > 0 { 8 } else { 0 }),
+            row![
+                Element::from(peer.view(index)).map(move |i| match peer_addr {
+                    Some(peer_addr) => Message::ForPeerByAddr(peer_addr, i),
+                    None => Message::ForPeerByIndex(index, i),
+                }),
+                horizontal_space().width(8),
+                peer.peer_addr()
+                    .and_then(|i| self.traffic.peer(i))
+                    .map_or_else(
+                        || horizontal_space().width(64).into(),
+                        History::view_sparkline
+                    ),
+            ],
+        ]
+        .into()
+    }
+
+    fn view_list(&self, shown_count: usize) -> Element<'_, Message> {
+        column![
+            Element::from(self.list.view(())).map(Message::ForList),
+            row![
+                horizontal_space().width(48 + 8),
+                if self.list.is_filtered() {
+                    text!("{shown_count} of {} peers shown.", self.peers.len())
+                } else {
+                    text!("")
+                },
+            ],
+        ]
+        .into()
+    }
+
+    fn view_ports<'a>(&'a self, storage: &Storage) -> Element<'a, Message> {
+        let port_range = self.port_range.as_ref().map_or_else(
+            || {
+                storage
+                    .config
+                    .port_range
+                    .as_ref()
+                    .map(PortRange::to_string)
+                    .unwrap_or_default()
+            },
+            |i| i.value.clone(),
+        );
+
+        let pooled_count = storage.config.port_range.map(|range| {
+            let count = self
+                .peers
+                .iter()
+                .filter_map(peer::State::bound_addr)
+                .filter(|i| range.contains(i.port()))
+                .count();
+
+            (count, range.ports().len())
+        });
+
+        column![
+            row![
+                text!("Local ports").width(96),
+                horizontal_space().width(8),
+                text_input("Any", &port_range)
+                    .on_input(Message::UpdatePortRange)
+                    .on_submit(Message::ChangePortRange),
+                horizontal_space().width(8),
+                checkbox("Keep per peer", storage.config.stable_ports)
+                    .on_toggle(Message::ToggleStablePorts),
+                horizontal_space().width(8),
+                button(text!("Apply")).on_press(Message::ChangePortRange),
+            ],
+            row![
+                horizontal_space().width(96 + 8),
+                match (
+                    self.port_range.as_ref().and_then(|i| i.error.as_ref()),
+                    pooled_count,
+                ) {
+                    (Some(e), _) => text!("{e}").style(text::danger),
+                    (None, None) => text!(""),
+                    (None, Some((used, total))) if used >= total => {
+                        text!("Every port of the range is in use.").style(text::danger)
+                    }
+                    (None, Some((used, total))) => text!("{used} of {total} ports are in use."),
+                },
+            ],
+        ]
+        .into()
     }
 }
 
 impl IcedComponent for State {
     type Message = Message;
     type TaskMessage = super::Message;
-    type ExtraUpdateArgs<'a> = &'a broadcast::Sender<CommandMessage>;
-    type ExtraViewArgs<'a> = ();
+    type ExtraUpdateArgs<'a> = (&'a RelayHandle, &'a mut Storage);
+    type ExtraViewArgs<'a> = &'a Storage;
     type ExtraSubscriptionArgs<'a> = ();
 
     fn update(
         &mut self,
         message: Self::Message,
-        command_snd: Self::ExtraUpdateArgs<'_>,
+        (relay_handle, storage): Self::ExtraUpdateArgs<'_>,
     ) -> Task<Self::TaskMessage> {
         match message {
             Message::CopyRelayAddr => {
                 return clipboard::write(format!("{}", self.relay_addr));
             }
 
-            Message::Disconnect => {
-                command_snd.send(CommandMessage::DisconnectAll).unwrap();
+            Message::CopyInvite => {
+                return clipboard::write(self.invite(storage));
             }
 
-            Message::UpdateFwdAddr(i) => {
-                self.fwd_addr = i;
+            Message::ToggleQr => {
+                self.toggle_qr(storage);
             }
 
-            Message::ChangeFwdAddr => {
-                let fwd_addr = self.fwd_addr.trim();
-
-                let addr = match fwd_addr.parse() {
-                    Ok(addr) => addr,
-                    Err(e) => {
-                        if let Ok(i) = fwd_addr.parse() {
-                            addr!(LOCAL_IP:i)
-                        } else {
-                            eprintln!("Invalid forward address {fwd_addr}: {e}");
-                            return Task::none();
-                        }
-                    }
-                };
+            Message::ForQr(message) => {
+                if let Some(qr) = &mut self.qr {
+                    return qr.update(message, ());
+                }
+            }
+
+            Message::Disconnect => {
+                relay_handle.disconnect_all().unwrap();
+            }
 
-                command_snd
-                    .send(CommandMessage::ChangeFwdAddr(addr))
-                    .unwrap();
+            Message::UpdateFwdAddr(_)
+            | Message::ChangeFwdAddr
+            | Message::OnFwdAddrChanged(_)
+            | Message::OnFwdAddrResolved(..)
+            | Message::OnFwdResolveFailed(..)
+            | Message::ChangeBindIp(_)
+            | Message::OnBindIpChanged(_)
+            | Message::UpdatePortRange(_)
+            | Message::ChangePortRange
+            | Message::OnPortRangeChanged(_)
+            | Message::ToggleStablePorts(_)
+            | Message::UpdateIdleAfter(_)
+            | Message::UpdateRemoveIdleAfter(_)
+            | Message::ChangeIdle => {
+                self.update_settings(message, relay_handle, storage);
             }
 
             Message::AddPeer => {
                 self.peers.push(peer::State::default());
             }
 
+            Message::ToggleImport => {
+                self.import = match self.import {
+                    Some(_) => None,
+                    None => Some(Box::new(import::State::new())),
+                };
+            }
+
+            Message::ForImport(message) => {
+                if let Some(import) = &mut self.import {
+                    return import.update(message, self.relay_addr);
+                }
+            }
+
+            Message::ImportPeers(saved_peers) => {
+                self.import = None;
+                return self.connect_saved_peers(relay_handle, &storage.config, saved_peers);
+            }
+
+            Message::PasteInvite => {
+                return clipboard::read().map(|i| Message::OnInvitePasted(i).into());
+            }
+
+            Message::OnInvitePasted(code) => {
+                return self.paste_invite(relay_handle, storage, code);
+            }
+
+            Message::ReloadPeers => {
+                return self.reload_peers(relay_handle, storage);
+            }
+
+            Message::SavePeers => {
+                self.save_peers(storage);
+            }
+
+            Message::ForList(message) => {
+                return self.list.update(message, ());
+            }
+
             Message::ForPeerByIndex(index, message) => {
-                return self.peers[index]
-                    .update(message, (command_snd, self.relay_addr))
-                    .map(move |i| super::Message::ForPeerByIndex(index, i));
+                return self.update_peer(index, message, relay_handle, &storage.config);
             }
 
             Message::ForPeerByAddr(peer_addr, message) => {
-                if let Some((index, peer)) = self
-                    .peers
-                    .iter_mut()
-                    .enumerate()
-                    .find(|(_, i)| i.compare_peer(peer_addr))
-                {
-                    return peer
-                        .update(message, (command_snd, self.relay_addr))
-                        .map(move |i| super::Message::ForPeerByIndex(index, i));
+                let Some(index) = self.find_or_add_peer(peer_addr, &message) else {
+                    return Task::none();
+                };
+
+                if let peer::Message::OnBound(local_addr) = message {
+                    self.remember_port(index, peer_addr, local_addr.port(), storage);
                 }
 
-                eprintln!("non-existent peer {peer_addr} ignored: {message:?} @ {self:?}");
+                return self.update(
+                    Message::ForPeerByIndex(index, message),
+                    (relay_handle, storage),
+                );
+            }
+
+            Message::OnPeerTraffic(peer_addr, stats) => {
+                self.traffic.update(peer_addr, stats);
+            }
+
+            Message::OnTrafficTick => {
+                let peers = &self.peers;
+                self.traffic
+                    .sample(|peer_addr| peers.iter().any(|i| i.compare_peer(peer_addr)));
             }
         }
 
         Task::none()
     }
 
-    fn view<'a>(&'a self, _extra: Self::ExtraViewArgs<'_>) -> Element<'a, Self::Message> {
+    fn view<'a>(&'a self, storage: Self::ExtraViewArgs<'_>) -> Element<'a, Self::Message> {
+        let order = self.list.order(&self.peers, &self.traffic);
+
         column![
             row![
                 text!("Available at").width(96),
@@ -125,18 +848,48 @@ impl IcedComponent for State {
                 horizontal_space().width(8),
                 button(text!("Copy")).on_press(Message::CopyRelayAddr),
                 horizontal_space().width(8),
+                button(text!("Copy invite")).on_press(Message::CopyInvite),
+                horizontal_space().width(8),
+                button(text!("QR code")).on_press(Message::ToggleQr),
+                horizontal_space().width(8),
                 button(text!("Disconnect")).on_press(Message::Disconnect),
             ],
             vertical_space().height(8),
+            self.qr.as_ref().map_or_else(
+                || column![].into(),
+                |i| Element::from(column![
+                    Element::from(i.view(())).map(Message::ForQr),
+                    vertical_space().height(16),
+                ])
+            ),
             row![
                 text!("Forward to").width(96),
                 horizontal_space().width(8),
-                text_input("127.0.0.1:12345", &self.fwd_addr)
-                    .on_input(Message::UpdateFwdAddr)
-                    .on_submit(Message::ChangeFwdAddr),
+                self.fwd_addr.view(
+                    "127.0.0.1:12345",
+                    Message::UpdateFwdAddr,
+                    Message::ChangeFwdAddr
+                ),
                 horizontal_space().width(8),
-                button(text!("Apply")).on_press(Message::ChangeFwdAddr),
+                button(text!("Apply"))
+                    .on_press_maybe(self.fwd_addr.is_valid().then_some(Message::ChangeFwdAddr)),
             ],
+            row![
+                horizontal_space().width(96 + 8),
+                match &self.fwd_status {
+                    None => text!(""),
+                    Some(Ok(i)) => text!("Resolved to {i}"),
+                    Some(Err(e)) => text!("Could not resolve: {e}"),
+                },
+            ],
+            vertical_space().height(8),
+            self.view_bind(storage),
+            vertical_space().height(8),
+            self.view_ports(storage),
+            vertical_space().height(8),
+            self.view_idle(storage),
+            vertical_space().height(8),
+            self.traffic.relay.view_charts(),
             vertical_space().height(24),
             row![
                 text!("Peers").width(48),
@@ -147,17 +900,40 @@ impl IcedComponent for State {
                         .all(|i| !i.is_uncommitted())
                         .then_some(Message::AddPeer)
                 ),
+                horizontal_space().width(8),
+                button(text!("Import")).on_press(Message::ToggleImport),
+                horizontal_space().width(8),
+                button(text!("Paste invite")).on_press(Message::PasteInvite),
+                horizontal_space(),
+                button(text!("Reload saved"))
+                    .on_press_maybe(self.profile.is_some().then_some(Message::ReloadPeers)),
+                horizontal_space().width(8),
+                button(text!("Save"))
+                    .on_press_maybe(self.profile.is_some().then_some(Message::SavePeers)),
             ],
             vertical_space().height(8),
-            scrollable(column(self.peers.iter().enumerate().map(
-                |(index, peer)| {
-                    column![
-                        vertical_space().height(if index > 0 { 8 } else { 0 }),
-                        Element::from(peer.view(index))
-                            .map(move |i| Message::ForPeerByIndex(index, i)),
-                    ]
-                    .into()
-                }
+            self.invite_error.as_ref().map_or_else(
+                || column![].into(),
+                |e| Element::from(column![
+                    text!("Could not paste the invite: {e}").style(text::danger),
+                    vertical_space().height(8),
+                ])
+            ),
+            self.save_error.as_ref().map_or_else(
+                || column![].into(),
+                |e| Element::from(column![
+                    text!("{e}").style(text::danger),
+                    vertical_space().height(8),
+                ])
+            ),
+            self.import.as_ref().map_or_else(
+                || column![].into(),
+                |i| Element::from(i.view(())).map(Message::ForImport)
+            ),
+            self.view_list(order.len()),
+            vertical_space().height(8),
+            scrollable(column(order.into_iter().enumerate().map(
+                |(position, index)| self.view_peer(position, index, &self.peers[index])
             )))
             .height(Length::Fill),
         ]
@@ -1,9 +1,10 @@
 use iced::widget::{button, column, text, vertical_space};
 use iced::{Element, Task};
-use tokio::sync::broadcast;
 
+use turn_relay_worker::RelayHandle;
+
+use crate::gui::storage::Storage;
 use crate::gui::types::IcedComponent;
-use crate::worker::CommandMessage;
 
 #[derive(Debug, Clone)]
 pub enum Message {
@@ -13,13 +14,17 @@ pub enum Message {
 #[derive(Debug, Clone)]
 pub struct State {
     pub server: String,
+    pub profile: Option<String>,
+    pub fwd_addr: Option<String>,
     force_disconnect: bool,
 }
 
 impl State {
-    pub const fn new(server: String) -> Self {
+    pub const fn new(server: String, profile: Option<String>, fwd_addr: Option<String>) -> Self {
         Self {
             server,
+            profile,
+            fwd_addr,
             force_disconnect: false,
         }
     }
@@ -28,14 +33,14 @@ impl State {
 impl IcedComponent for State {
     type Message = Message;
     type TaskMessage = super::Message;
-    type ExtraUpdateArgs<'a> = &'a broadcast::Sender<CommandMessage>;
-    type ExtraViewArgs<'a> = ();
+    type ExtraUpdateArgs<'a> = (&'a RelayHandle, &'a mut Storage);
+    type ExtraViewArgs<'a> = &'a Storage;
     type ExtraSubscriptionArgs<'a> = ();
 
     fn update(
         &mut self,
         message: Self::Message,
-        command_snd: Self::ExtraUpdateArgs<'_>,
+        (relay_handle, _storage): Self::ExtraUpdateArgs<'_>,
     ) -> Task<Self::TaskMessage> {
         match message {
             Message::Disconnect => {
@@ -43,7 +48,7 @@ impl IcedComponent for State {
                     return Task::done(super::Message::ToDisconnected);
                 }
 
-                command_snd.send(CommandMessage::DisconnectAll).unwrap();
+                relay_handle.disconnect_all().unwrap();
 
                 self.force_disconnect = true;
             }
@@ -52,7 +57,7 @@ impl IcedComponent for State {
         Task::none()
     }
 
-    fn view<'a>(&'a self, _extra: Self::ExtraViewArgs<'_>) -> Element<'a, Self::Message> {
+    fn view<'a>(&'a self, _storage: Self::ExtraViewArgs<'_>) -> Element<'a, Self::Message> {
         column![
             text!("Connecting..."),
             vertical_space().height(24),
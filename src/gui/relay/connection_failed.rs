@@ -1,9 +1,10 @@
 use iced::widget::{button, column, text, vertical_space};
 use iced::{Element, Task};
-use tokio::sync::broadcast;
 
+use turn_relay_worker::{RelayError, RelayHandle};
+
+use crate::gui::storage::Storage;
 use crate::gui::types::IcedComponent;
-use crate::worker::CommandMessage;
 
 #[derive(Debug, Clone)]
 pub enum Message {
@@ -13,39 +14,44 @@ pub enum Message {
 #[derive(Debug, Clone)]
 pub struct State {
     pub server: String,
-    why: String,
+    pub profile: Option<String>,
+    why: RelayError,
 }
 
 impl State {
-    pub const fn new(server: String, why: String) -> Self {
-        Self { server, why }
+    pub const fn new(server: String, profile: Option<String>, why: RelayError) -> Self {
+        Self {
+            server,
+            profile,
+            why,
+        }
     }
 }
 
 impl IcedComponent for State {
     type Message = Message;
     type TaskMessage = super::Message;
-    type ExtraUpdateArgs<'a> = &'a broadcast::Sender<CommandMessage>;
-    type ExtraViewArgs<'a> = ();
+    type ExtraUpdateArgs<'a> = (&'a RelayHandle, &'a mut Storage);
+    type ExtraViewArgs<'a> = &'a Storage;
     type ExtraSubscriptionArgs<'a> = ();
 
     fn update(
         &mut self,
         message: Self::Message,
-        _command_snd: Self::ExtraUpdateArgs<'_>,
+        _extra: Self::ExtraUpdateArgs<'_>,
     ) -> Task<Self::TaskMessage> {
         match message {
-            Message::Disconnect => {}
+            Message::Disconnect => Task::done(super::Message::ToDisconnected),
         }
-
-        Task::none()
     }
 
-    fn view<'a>(&'a self, _extra: Self::ExtraViewArgs<'_>) -> Element<'a, Self::Message> {
+    fn view<'a>(&'a self, _storage: Self::ExtraViewArgs<'_>) -> Element<'a, Self::Message> {
         column![
             text!("Connection to {} failed.", self.server),
             vertical_space().height(8),
-            text!("{}", self.why),
+            text!("{}.", self.why),
+            vertical_space().height(8),
+            text!("{}", self.why.hint()),
             vertical_space().height(24),
             button(text!("Back")).on_press(Message::Disconnect),
         ]
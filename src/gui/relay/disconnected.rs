@@ -1,24 +1,35 @@
 use std::mem::take;
 
 use iced::{
-    widget::{button, column, horizontal_space, row, text, text_input, vertical_space},
+    widget::{
+        button, checkbox, column, horizontal_space, pick_list, row, text, text_input,
+        vertical_space,
+    },
     Element, Task,
 };
-use tokio::sync::broadcast;
+
+use turn_relay_worker::RelayHandle;
 
 use crate::{
     gui::{
-        relay::{connected, connecting, connection_failed},
+        relay::{connected, connecting, connection_failed, locked},
+        storage::Storage,
         types::IcedComponent,
     },
-    worker::CommandMessage,
+    vault::Vault,
 };
 
 #[derive(Debug, Clone)]
 pub enum Message {
+    SelectProfile(String),
+    UpdateProfileName(String),
+    SaveProfile,
+    DeleteProfile,
     UpdateServer(String),
     UpdateUsername(String),
     UpdatePassword(String),
+    ToggleRememberPassword(bool),
+    UpdateVaultPassphrase(String),
     Connect,
 }
 
@@ -27,48 +38,164 @@ pub struct State {
     pub server: String,
     username: String,
     password: String,
+    profile: Option<String>,
+    profile_name: String,
+    remember_password: bool,
+    vault_passphrase: String,
+    vault_error: Option<String>,
+    save_error: Option<String>,
 }
 
 impl State {
-    pub fn new(server: String) -> Self {
+    pub fn new(server: String, profile: Option<String>) -> Self {
         Self {
             server,
+            profile_name: profile.clone().unwrap_or_default(),
+            profile,
             ..Default::default()
         }
     }
+
+    /// Stores or forgets the password of a profile, and tells whether it
+    /// succeeded. Otherwise, the reason is shown next to the passphrase.
+    fn store_password(&mut self, storage: &mut Storage, profile: &str) -> bool {
+        self.vault_error = None;
+
+        if !self.remember_password {
+            if let Some(vault) = &mut storage.vault {
+                vault.remove_password(profile);
+            }
+
+            return self.save_vault(storage);
+        }
+
+        if storage.vault.is_none() {
+            if self.vault_passphrase.is_empty() {
+                self.vault_error =
+                    Some("A vault passphrase is required to remember passwords".into());
+                return false;
+            }
+
+            match Vault::open_or_create(&take(&mut self.vault_passphrase)) {
+                Ok(i) => {
+                    if let Err(e) = storage.set_vault(i) {
+                        self.vault_error = Some(format!("{e:#}"));
+                        return false;
+                    }
+                }
+                Err(e) => {
+                    self.vault_error = Some(format!("Could not unlock the credentials: {e:#}"));
+                    return false;
+                }
+            }
+        }
+
+        if let Some(vault) = &mut storage.vault {
+            vault.set_password(profile, self.password.clone());
+        }
+
+        self.save_vault(storage)
+    }
+
+    /// Saves the vault, and tells whether it succeeded. Otherwise, the reason
+    /// is shown next to the passphrase.
+    fn save_vault(&mut self, storage: &Storage) -> bool {
+        if let Err(e) = storage.save_vault() {
+            self.vault_error = Some(format!("{e:#}"));
+            return false;
+        }
+
+        true
+    }
+}
+
+impl From<locked::State> for State {
+    fn from(_value: locked::State) -> Self {
+        Self::default()
+    }
 }
 
 impl From<connecting::State> for State {
     fn from(value: connecting::State) -> Self {
-        Self::new(value.server)
+        Self::new(value.server, value.profile)
     }
 }
 
 impl From<connection_failed::State> for State {
     fn from(value: connection_failed::State) -> Self {
-        Self::new(value.server)
+        Self::new(value.server, value.profile)
     }
 }
 
 impl From<connected::State> for State {
     fn from(value: connected::State) -> Self {
-        Self::new(value.server)
+        Self::new(value.server, value.profile)
     }
 }
 
 impl IcedComponent for State {
     type Message = Message;
     type TaskMessage = super::Message;
-    type ExtraUpdateArgs<'a> = &'a broadcast::Sender<CommandMessage>;
-    type ExtraViewArgs<'a> = ();
+    type ExtraUpdateArgs<'a> = (&'a RelayHandle, &'a mut Storage);
+    type ExtraViewArgs<'a> = &'a Storage;
     type ExtraSubscriptionArgs<'a> = ();
 
     fn update(
         &mut self,
         message: Self::Message,
-        _command_snd: Self::ExtraUpdateArgs<'_>,
+        (_relay_handle, storage): Self::ExtraUpdateArgs<'_>,
     ) -> Task<Self::TaskMessage> {
         match message {
+            Message::SelectProfile(i) => {
+                if let Some(profile) = storage.config.profile(&i) {
+                    self.server.clone_from(&profile.server);
+                    self.username.clone_from(&profile.username);
+
+                    let password = storage.vault.as_ref().and_then(|j| j.password(&i));
+                    self.remember_password = password.is_some();
+                    self.password = password.unwrap_or_default().to_string();
+
+                    self.profile_name.clone_from(&i);
+                    self.profile = Some(i);
+                }
+            }
+
+            Message::UpdateProfileName(i) => {
+                self.profile_name = i;
+            }
+
+            Message::SaveProfile => {
+                let name = self.profile_name.trim().to_string();
+
+                if name.is_empty() {
+                    return Task::none();
+                }
+
+                storage.config.upsert_profile(
+                    &name,
+                    self.server.trim().to_string(),
+                    self.username.clone(),
+                );
+                self.save_error = storage.save_config().err().map(|e| format!("{e:#}"));
+                self.store_password(storage, &name);
+
+                self.profile_name.clone_from(&name);
+                self.profile = Some(name);
+            }
+
+            Message::DeleteProfile => {
+                if let Some(name) = self.profile.take() {
+                    storage.config.remove_profile(&name);
+                    self.save_error = storage.save_config().err().map(|e| format!("{e:#}"));
+                    self.vault_error = None;
+
+                    if let Some(vault) = &mut storage.vault {
+                        vault.remove_password(&name);
+                        self.save_vault(storage);
+                    }
+                }
+            }
+
             Message::UpdateServer(i) => {
                 self.server = i;
             }
@@ -81,11 +208,28 @@ impl IcedComponent for State {
                 self.password = i;
             }
 
+            Message::ToggleRememberPassword(i) => {
+                self.remember_password = i;
+            }
+
+            Message::UpdateVaultPassphrase(i) => {
+                self.vault_passphrase = i;
+                self.vault_error = None;
+            }
+
             Message::Connect => {
+                if let Some(profile) = self.profile.clone() {
+                    if !self.store_password(storage, &profile) {
+                        return Task::none();
+                    }
+                }
+
                 return Task::done(super::Message::ToConnecting {
                     server: self.server.trim().to_string(),
                     username: take(&mut self.username),
                     password: take(&mut self.password),
+                    profile: self.profile.clone(),
+                    fwd_addr: storage.config.fwd_addr.clone(),
                 });
             }
         }
@@ -93,8 +237,22 @@ impl IcedComponent for State {
         Task::none()
     }
 
-    fn view<'a>(&'a self, _extra: Self::ExtraViewArgs<'_>) -> Element<'a, Self::Message> {
+    fn view<'a>(&'a self, storage: Self::ExtraViewArgs<'_>) -> Element<'a, Self::Message> {
         column![
+            row![
+                text!("Profile").width(96),
+                horizontal_space().width(8),
+                pick_list(
+                    storage.config.profile_names(),
+                    self.profile.clone(),
+                    Message::SelectProfile
+                )
+                .placeholder("None"),
+                horizontal_space().width(8),
+                button(text!("Delete"))
+                    .on_press_maybe(self.profile.is_some().then_some(Message::DeleteProfile)),
+            ],
+            vertical_space().height(8),
             row![
                 text!("Server").width(96),
                 horizontal_space().width(8),
@@ -112,11 +270,57 @@ impl IcedComponent for State {
                 horizontal_space().width(8),
                 text_input("abc123", &self.password)
                     .on_input(Message::UpdatePassword)
-                    .on_submit(Message::Connect),
+                    .on_submit(Message::Connect)
+                    .secure(true),
+            ],
+            vertical_space().height(8),
+            row![
+                horizontal_space().width(96 + 8),
+                checkbox("Remember password", self.remember_password)
+                    .on_toggle(Message::ToggleRememberPassword),
+            ]
+            .push_maybe((self.remember_password && storage.vault.is_none()).then(
+                || {
+                    row![
+                        horizontal_space().width(8),
+                        text_input(
+                            if Vault::exists() {
+                                "Vault passphrase"
+                            } else {
+                                "New vault passphrase"
+                            },
+                            &self.vault_passphrase
+                        )
+                        .on_input(Message::UpdateVaultPassphrase)
+                        .secure(true),
+                    ]
+                }
+            )),
+            row![
+                horizontal_space().width(96 + 8),
+                self.vault_error
+                    .as_ref()
+                    .map_or_else(|| text!(""), |e| text!("{e}").style(text::danger)),
             ],
             vertical_space().height(24),
-            button(text!("Connect")).on_press(Message::Connect),
+            row![
+                button(text!("Connect")).on_press(Message::Connect),
+                horizontal_space(),
+                text_input("Profile name", &self.profile_name)
+                    .on_input(Message::UpdateProfileName)
+                    .on_submit(Message::SaveProfile)
+                    .width(160),
+                horizontal_space().width(8),
+                button(text!("Save as profile")).on_press_maybe(
+                    (!self.profile_name.trim().is_empty()).then_some(Message::SaveProfile)
+                ),
+            ],
         ]
+        .push_maybe(
+            self.save_error
+                .as_ref()
+                .map(|e| column![vertical_space().height(8), text!("{e}").style(text::danger),]),
+        )
         .into()
     }
 }
@@ -0,0 +1,188 @@
+use std::net::SocketAddr;
+
+use iced::widget::{
+    button, column, horizontal_space, row, text, text_editor, vertical_space, Column,
+};
+use iced::{clipboard, Element, Task};
+
+use turn_relay_worker::{ReturnMode, SourcePolicy, LOCAL_IP};
+
+use crate::config::SavedPeer;
+use crate::gui::addr_input::parse_addr;
+use crate::gui::relay::connected;
+use crate::gui::types::IcedComponent;
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    Edit(text_editor::Action),
+    Paste,
+    OnPaste(Option<String>),
+    Import,
+    Cancel,
+}
+
+#[derive(Debug)]
+pub struct State {
+    content: text_editor::Content,
+    entries: Vec<(String, Result<SavedPeer, String>)>,
+    is_paste_failed: bool,
+}
+
+impl Clone for State {
+    fn clone(&self) -> Self {
+        Self {
+            content: text_editor::Content::with_text(&self.content.text()),
+            entries: self.entries.clone(),
+            is_paste_failed: self.is_paste_failed,
+        }
+    }
+}
+
+impl State {
+    pub fn new() -> Self {
+        Self {
+            content: text_editor::Content::new(),
+            entries: vec![],
+            is_paste_failed: false,
+        }
+    }
+
+    /// Parses a peer, as `ip:port` or `port` on the relay host, optionally
+    /// pinned to a local address with `=ip:port` or `=port`.
+    fn parse_entry(entry: &str, relay_addr: SocketAddr) -> Result<SavedPeer, String> {
+        let (peer_addr, local_addr) = entry.split_once('=').unwrap_or((entry, ""));
+
+        Ok(SavedPeer {
+            peer_addr: parse_addr(peer_addr, relay_addr.ip())
+                .map_err(|e| format!("Invalid peer address: {e}"))?,
+            local_addr: match local_addr.trim() {
+                "" => None,
+                i => Some(
+                    parse_addr(i, LOCAL_IP).map_err(|e| format!("Invalid local address: {e}"))?,
+                ),
+            },
+            fwd_addr: None,
+            return_mode: ReturnMode::default(),
+            source_policy: SourcePolicy::default(),
+            label: String::new(),
+            note: String::new(),
+        })
+    }
+
+    fn validate(&mut self, relay_addr: SocketAddr) {
+        let text = self.content.text();
+        let mut entries: Vec<(String, Result<SavedPeer, String>)> = vec![];
+
+        for entry in text.split(['\n', ',']).map(str::trim) {
+            if entry.is_empty() {
+                continue;
+            }
+
+            let mut result = Self::parse_entry(entry, relay_addr);
+
+            if let Ok(peer) = &result {
+                if entries
+                    .iter()
+                    .any(|(_, i)| i.as_ref().is_ok_and(|i| i.peer_addr == peer.peer_addr))
+                {
+                    result = Err(format!("{} is listed twice", peer.peer_addr));
+                }
+            }
+
+            entries.push((entry.to_string(), result));
+        }
+
+        self.entries = entries;
+    }
+
+    fn valid_peers(&self) -> Vec<SavedPeer> {
+        self.entries
+            .iter()
+            .filter_map(|(_, i)| i.as_ref().ok().cloned())
+            .collect()
+    }
+}
+
+impl IcedComponent for State {
+    type Message = Message;
+    type TaskMessage = super::Message;
+    type ExtraUpdateArgs<'a> = SocketAddr;
+    type ExtraViewArgs<'a> = ();
+    type ExtraSubscriptionArgs<'a> = ();
+
+    fn update(
+        &mut self,
+        message: Self::Message,
+        relay_addr: Self::ExtraUpdateArgs<'_>,
+    ) -> Task<Self::TaskMessage> {
+        match message {
+            Message::Edit(action) => {
+                self.is_paste_failed = false;
+                self.content.perform(action);
+                self.validate(relay_addr);
+            }
+
+            Message::Paste => {
+                return clipboard::read()
+                    .map(|i| connected::Message::ForImport(Message::OnPaste(i)).into());
+            }
+
+            Message::OnPaste(Some(i)) => {
+                self.is_paste_failed = false;
+                self.content = text_editor::Content::with_text(&i);
+                self.validate(relay_addr);
+            }
+
+            Message::OnPaste(None) => {
+                self.is_paste_failed = true;
+            }
+
+            Message::Import => {
+                return Task::done(connected::Message::ImportPeers(self.valid_peers()).into());
+            }
+
+            Message::Cancel => {
+                return Task::done(connected::Message::ToggleImport.into());
+            }
+        }
+
+        Task::none()
+    }
+
+    fn view<'a>(&'a self, _extra: Self::ExtraViewArgs<'_>) -> Element<'a, Self::Message> {
+        let valid_count = self.entries.iter().filter(|(_, i)| i.is_ok()).count();
+
+        let errors = self.entries.iter().filter_map(|(entry, i)| {
+            i.as_ref()
+                .err()
+                .map(|e| text!("{entry}: {e}").style(text::danger).into())
+        });
+
+        column![
+            text!("Peers on separate lines or separated by commas, as ip:port, port, or ip:port=local port"),
+            vertical_space().height(8),
+            text_editor(&self.content)
+                .placeholder("123.45.67.89:12345")
+                .on_action(Message::Edit)
+                .height(160),
+            vertical_space().height(8),
+            Column::with_children(errors).push_maybe(self.is_paste_failed.then(|| {
+                text!("Clipboard is empty, or does not contain text").style(text::danger)
+            })),
+            vertical_space().height(8),
+            row![
+                button(text!("Paste")).on_press(Message::Paste),
+                horizontal_space(),
+                button(text!("Cancel")).on_press(Message::Cancel),
+                horizontal_space().width(8),
+                button(match valid_count {
+                    1 => text!("Import 1 peer"),
+                    i => text!("Import {i} peers"),
+                })
+                .on_press_maybe((valid_count > 0).then_some(Message::Import)),
+            ],
+            vertical_space().height(16),
+        ]
+        .into()
+    }
+}
@@ -0,0 +1,346 @@
+use std::cmp::Reverse;
+use std::fmt::Display;
+
+use iced::widget::{horizontal_space, pick_list, row, text, text_input};
+use iced::{Element, Task};
+
+use crate::gui::peer;
+use crate::gui::relay::traffic::Traffic;
+use crate::gui::types::IcedComponent;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SortKey {
+    #[default]
+    Added,
+    Label,
+    Address,
+    State,
+    Traffic,
+    LastSeen,
+}
+
+impl SortKey {
+    const ALL: [Self; 6] = [
+        Self::Added,
+        Self::Label,
+        Self::Address,
+        Self::State,
+        Self::Traffic,
+        Self::LastSeen,
+    ];
+}
+
+impl Display for SortKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Added => "Order added",
+            Self::Label => "Name",
+            Self::Address => "Address",
+            Self::State => "State",
+            Self::Traffic => "Traffic",
+            Self::LastSeen => "Last seen",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StateFilter {
+    #[default]
+    All,
+    Failed,
+    Idle,
+}
+
+impl StateFilter {
+    const ALL: [Self; 3] = [Self::All, Self::Failed, Self::Idle];
+
+    const fn matches(self, peer: &peer::State) -> bool {
+        match self {
+            Self::All => true,
+            Self::Failed => peer.is_failed(),
+            Self::Idle => peer.is_idle(),
+        }
+    }
+}
+
+impl Display for StateFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::All => "All states",
+            Self::Failed => "Failed only",
+            Self::Idle => "Idle only",
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    UpdateSearch(String),
+    ChangeSort(SortKey),
+    ChangeStateFilter(StateFilter),
+}
+
+/// How the peer list is sorted and filtered. It only changes the view; The
+/// peers keep their place, and their number.
+#[derive(Debug, Clone, Default)]
+pub struct State {
+    search: String,
+    sort: SortKey,
+    filter: StateFilter,
+}
+
+/// Orders failed peers first, and ready ones last.
+const fn state_rank(peer: &peer::State) -> u8 {
+    match peer {
+        peer::State::Failed(_) => 0,
+        peer::State::Intermediate | peer::State::EditingPeer(_) | peer::State::EditingLocal(_) => 1,
+        peer::State::Waiting(_) => 2,
+        peer::State::Paused(_) => 3,
+        peer::State::Ready(_) if peer.is_idle() => 4,
+        peer::State::Ready(_) => 5,
+    }
+}
+
+impl State {
+    pub fn is_filtered(&self) -> bool {
+        !self.search.trim().is_empty() || !matches!(self.filter, StateFilter::All)
+    }
+
+    /// Whether the name, note, or an address of the peer contains the search.
+    fn matches_search(&self, peer: &peer::State) -> bool {
+        let search = self.search.trim().to_lowercase();
+
+        if search.is_empty() {
+            return true;
+        }
+
+        let annotation = peer.annotation();
+
+        [
+            annotation.label.to_lowercase(),
+            annotation.note.to_lowercase(),
+            peer.peer_addr().map(|i| i.to_string()).unwrap_or_default(),
+            peer.bound_addr().map(|i| i.to_string()).unwrap_or_default(),
+        ]
+        .iter()
+        .any(|i| i.contains(&search))
+    }
+
+    /// Returns the indices of the peers to show, in order. Peers that are
+    /// being added are always shown, at the end.
+    pub fn order(&self, peers: &[peer::State], traffic: &Traffic) -> Vec<usize> {
+        let (mut indices, uncommitted): (Vec<_>, Vec<_>) =
+            (0..peers.len()).partition(|i| !peers[*i].is_uncommitted());
+
+        indices.retain(|i| self.filter.matches(&peers[*i]) && self.matches_search(&peers[*i]));
+
+        // The sort is stable, so that equal peers stay in the order added.
+        match self.sort {
+            SortKey::Added => {}
+            SortKey::Label => indices.sort_by_cached_key(|i| {
+                let label = &peers[*i].annotation().label;
+                (label.is_empty(), label.to_lowercase())
+            }),
+            SortKey::Address => indices.sort_by_key(|i| peers[*i].peer_addr()),
+            SortKey::State => indices.sort_by_key(|i| state_rank(&peers[*i])),
+            SortKey::Traffic => {
+                indices.sort_by_key(|i| Reverse(peers[*i].peer_addr().map(|i| traffic.rate(i))));
+            }
+            SortKey::LastSeen => indices
+                .sort_by_key(|i| Reverse(peers[*i].peer_addr().and_then(|i| traffic.last_seen(i)))),
+        }
+
+        indices.extend(uncommitted);
+        indices
+    }
+}
+
+impl IcedComponent for State {
+    type Message = Message;
+    type TaskMessage = super::Message;
+    type ExtraUpdateArgs<'a> = ();
+    type ExtraViewArgs<'a> = ();
+    type ExtraSubscriptionArgs<'a> = ();
+
+    fn update(
+        &mut self,
+        message: Self::Message,
+        _extra: Self::ExtraUpdateArgs<'_>,
+    ) -> Task<Self::TaskMessage> {
+        match message {
+            Message::UpdateSearch(i) => {
+                self.search = i;
+            }
+
+            Message::ChangeSort(i) => {
+                self.sort = i;
+            }
+
+            Message::ChangeStateFilter(i) => {
+                self.filter = i;
+            }
+        }
+
+        Task::none()
+    }
+
+    fn view<'a>(&'a self, _extra: Self::ExtraViewArgs<'_>) -> Element<'a, Self::Message> {
+        row![
+            text!("Show").width(48),
+            horizontal_space().width(8),
+            text_input("Search names, notes and addresses", &self.search)
+                .on_input(Message::UpdateSearch),
+            horizontal_space().width(8),
+            pick_list(
+                StateFilter::ALL,
+                Some(self.filter),
+                Message::ChangeStateFilter
+            ),
+            horizontal_space().width(8),
+            text!("Sort by"),
+            horizontal_space().width(8),
+            pick_list(SortKey::ALL, Some(self.sort), Message::ChangeSort),
+        ]
+        .into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+    use std::thread::sleep;
+    use std::time::Duration;
+
+    use turn_relay_worker::{addr, PeerError, RelayHandle, ReturnMode, SourcePolicy, TrafficStats};
+
+    use super::*;
+    use crate::config::Config;
+    use crate::gui::peer::types::Annotation;
+
+    fn update(peer: &mut peer::State, message: peer::Message) {
+        let _ = peer.update(
+            message,
+            (
+                &RelayHandle::new(),
+                addr!(203, 0, 113, 1:3478),
+                &Config::default(),
+            ),
+        );
+    }
+
+    fn waiting(label: &str, note: &str, peer_addr: SocketAddr) -> peer::State {
+        peer::State::new_waiting(
+            peer_addr,
+            None,
+            None,
+            ReturnMode::default(),
+            SourcePolicy::default(),
+            Annotation {
+                label: label.to_string(),
+                note: note.to_string(),
+            },
+        )
+    }
+
+    fn ready(label: &str, peer_addr: SocketAddr, local_addr: SocketAddr) -> peer::State {
+        let mut peer = waiting(label, "", peer_addr);
+        update(&mut peer, peer::Message::OnBound(local_addr));
+        update(&mut peer, peer::Message::OnPermissionGranted);
+        update(&mut peer, peer::Message::ToReady);
+        peer
+    }
+
+    /// Peers in every state, added in a different order than any sort.
+    fn peers() -> Vec<peer::State> {
+        let mut failed = waiting("alice", "", addr!(192, 0, 2, 2:1));
+        update(
+            &mut failed,
+            peer::Message::OnPermissionDenied(PeerError::Forbidden),
+        );
+
+        let mut idle = ready("Bob", addr!(192, 0, 2, 3:1), addr!(127, 0, 0, 1:40002));
+        update(&mut idle, peer::Message::OnIdle(true));
+
+        let mut paused = ready("dave", addr!(192, 0, 2, 5:1), addr!(127, 0, 0, 1:40004));
+        update(&mut paused, peer::Message::OnPaused);
+
+        vec![
+            ready("Charlie", addr!(192, 0, 2, 4:1), addr!(127, 0, 0, 1:40003)),
+            failed,
+            waiting("", "Work laptop", addr!(192, 0, 2, 1:1)),
+            idle,
+            peer::State::default(),
+            paused,
+        ]
+    }
+
+    fn stats(bytes: u64) -> TrafficStats {
+        let mut stats = TrafficStats::default();
+        stats.bytes_in = bytes;
+        stats
+    }
+
+    /// Bob relays the most, but Charlie was seen last.
+    fn traffic() -> Traffic {
+        let mut traffic = Traffic::default();
+        traffic.update(addr!(192, 0, 2, 3:1), stats(1000));
+        sleep(Duration::from_millis(10));
+        traffic.update(addr!(192, 0, 2, 4:1), stats(10));
+        traffic.sample(|_| true);
+        traffic
+    }
+
+    fn order(search: &str, sort: SortKey, filter: StateFilter) -> Vec<usize> {
+        let state = State {
+            search: search.to_string(),
+            sort,
+            filter,
+        };
+
+        state.order(&peers(), &traffic())
+    }
+
+    #[test]
+    fn sort_keys() {
+        let sort = |sort| order("", sort, StateFilter::All);
+
+        assert_eq!(sort(SortKey::Added), [0, 1, 2, 3, 5, 4]);
+        assert_eq!(sort(SortKey::Label), [1, 3, 0, 5, 2, 4]);
+        assert_eq!(sort(SortKey::Address), [2, 1, 3, 0, 5, 4]);
+        assert_eq!(sort(SortKey::State), [1, 2, 5, 3, 0, 4]);
+        assert_eq!(sort(SortKey::Traffic), [3, 0, 1, 2, 5, 4]);
+        assert_eq!(sort(SortKey::LastSeen), [0, 3, 1, 2, 5, 4]);
+    }
+
+    #[test]
+    fn state_filters() {
+        let filter = |filter| order("", SortKey::Added, filter);
+
+        assert_eq!(filter(StateFilter::All), [0, 1, 2, 3, 5, 4]);
+        assert_eq!(filter(StateFilter::Failed), [1, 4]);
+        assert_eq!(filter(StateFilter::Idle), [3, 4]);
+    }
+
+    #[test]
+    fn search_ignores_case() {
+        let search = |search| order(search, SortKey::Added, StateFilter::All);
+
+        assert_eq!(search("ALICE"), [1, 4]);
+        assert_eq!(search("  work "), [2, 4]);
+        assert_eq!(search("192.0.2.5"), [5, 4]);
+        assert_eq!(search(":40003"), [0, 4]);
+        assert_eq!(search("nobody"), [4]);
+    }
+
+    #[test]
+    fn uncommitted_peers_stay_last() {
+        let state = State {
+            sort: SortKey::Label,
+            ..State::default()
+        };
+        let mut peers = peers();
+        peers.insert(0, peer::State::default());
+
+        assert_eq!(state.order(&peers, &traffic()), [2, 4, 1, 6, 3, 0, 5]);
+    }
+}
@@ -0,0 +1,88 @@
+use std::mem::take;
+
+use iced::widget::{button, column, horizontal_space, row, text, text_input, vertical_space};
+use iced::{Element, Task};
+
+use turn_relay_worker::RelayHandle;
+
+use crate::gui::storage::Storage;
+use crate::gui::types::IcedComponent;
+use crate::vault::Vault;
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    UpdatePassphrase(String),
+    Unlock,
+    Skip,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct State {
+    passphrase: String,
+    error: Option<String>,
+}
+
+impl IcedComponent for State {
+    type Message = Message;
+    type TaskMessage = super::Message;
+    type ExtraUpdateArgs<'a> = (&'a RelayHandle, &'a mut Storage);
+    type ExtraViewArgs<'a> = &'a Storage;
+    type ExtraSubscriptionArgs<'a> = ();
+
+    fn update(
+        &mut self,
+        message: Self::Message,
+        (_relay_handle, storage): Self::ExtraUpdateArgs<'_>,
+    ) -> Task<Self::TaskMessage> {
+        match message {
+            Message::UpdatePassphrase(i) => {
+                self.passphrase = i;
+            }
+
+            Message::Unlock => match Vault::open(&take(&mut self.passphrase)) {
+                // The vault stays unlocked, but the error is shown until it is
+                // unlocked again or skipped.
+                Ok(i) => match storage.set_vault(i) {
+                    Ok(()) => return Task::done(super::Message::ToDisconnected),
+                    Err(e) => self.error = Some(format!("{e:#}")),
+                },
+                Err(e) => {
+                    self.error = Some(format!("{e:#}"));
+                }
+            },
+
+            Message::Skip => {
+                return Task::done(super::Message::ToDisconnected);
+            }
+        }
+
+        Task::none()
+    }
+
+    fn view<'a>(&'a self, _storage: Self::ExtraViewArgs<'_>) -> Element<'a, Self::Message> {
+        column![
+            text!("Saved passwords are locked."),
+            vertical_space().height(8),
+            row![
+                text!("Passphrase").width(96),
+                horizontal_space().width(8),
+                text_input("", &self.passphrase)
+                    .on_input(Message::UpdatePassphrase)
+                    .on_submit(Message::Unlock)
+                    .secure(true),
+            ],
+        ]
+        .push_maybe(
+            self.error
+                .as_ref()
+                .map(|i| column![vertical_space().height(8), text!("{i}")]),
+        )
+        .push(vertical_space().height(24))
+        .push(row![
+            button(text!("Unlock")).on_press(Message::Unlock),
+            horizontal_space().width(8),
+            button(text!("Skip")).on_press(Message::Skip),
+        ])
+        .into()
+    }
+}
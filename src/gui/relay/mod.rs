@@ -2,33 +2,48 @@ mod connected;
 mod connecting;
 mod connection_failed;
 mod disconnected;
+mod import;
+mod list;
+pub mod locked;
+mod qr;
+pub mod traffic;
 
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
 
-use tokio::sync::broadcast;
-
-use crate::{
-    gui::{macros::router_component, peer},
-    worker::CommandMessage,
+use turn_relay_worker::{
+    PortRange, RelayConfig, RelayError, RelayHandle, RetryPolicy, TrafficStats,
 };
 
+use crate::gui::{macros::router_component, peer, storage::Storage};
+
 router_component! {
     message enum Message {
         ForPeerByAddr(SocketAddr, peer::Message),
         ForPeerByIndex(usize, peer::Message),
         OnAllocated(SocketAddr),
-        OnConnectionFailed(String),
+        OnBindIpChanged(IpAddr),
+        OnConnecting(String),
+        OnConnectionFailed(RelayError),
         OnDisconnected,
+        OnFwdAddrChanged(SocketAddr),
+        OnFwdAddrResolved(String, SocketAddr),
+        OnFwdResolveFailed(String, String),
+        OnPeerTraffic(SocketAddr, TrafficStats),
+        OnPortRangeChanged(Option<PortRange>),
         OnRedirect(SocketAddr),
+        OnTrafficTick,
         ToConnecting {
             server: String,
             username: String,
             password: String,
+            profile: Option<String>,
+            fwd_addr: Option<String>,
         },
         ToDisconnected,
     }
 
     state enum State {
+        Locked(locked::State),
         Disconnected(disconnected::State),
         Connecting(connecting::State),
         ConnectionFailed(connection_failed::State),
@@ -37,12 +52,13 @@ router_component! {
 
     impl Default for Disconnected;
 
-    type ExtraUpdateArgs<'a> = &'a broadcast::Sender<CommandMessage>;
-    type ExtraViewArgs<'a> = ();
+    type ExtraUpdateArgs<'a> = (&'a RelayHandle, &'a mut Storage);
+    type ExtraViewArgs<'a> = &'a Storage;
     type ExtraSubscriptionArgs<'a> = ();
 
     fn update(..) {
         // ForPeerByAddr
+        given ForPeerByAddr ignore Locked;
         given ForPeerByAddr ignore Disconnected;
         given ForPeerByAddr ignore Connecting;
         given ForPeerByAddr ignore ConnectionFailed;
@@ -51,6 +67,7 @@ router_component! {
             pass Connected(connected::Message::ForPeerByAddr(addr, message));
 
         // ForPeerByIndex
+        given ForPeerByIndex ignore Locked;
         given ForPeerByIndex ignore Disconnected;
         given ForPeerByIndex ignore Connecting;
         given ForPeerByIndex ignore ConnectionFailed;
@@ -59,52 +76,151 @@ router_component! {
             pass Connected(connected::Message::ForPeerByIndex(index, message));
 
         // OnAllocated
+        given OnAllocated ignore Locked;
         given OnAllocated ignore Disconnected;
 
         given OnAllocated(relay_addr)
             turn Connecting(i)
-            into Connected(connected::State::new(i.server, relay_addr));
+            into Connected(connected::State::new(i.server, i.profile, i.fwd_addr, relay_addr));
 
         given OnAllocated ignore ConnectionFailed;
         given OnAllocated ignore Connected;
 
+        // OnBindIpChanged
+        given OnBindIpChanged ignore Locked;
+        given OnBindIpChanged ignore Disconnected;
+        given OnBindIpChanged ignore Connecting;
+        given OnBindIpChanged ignore ConnectionFailed;
+
+        given OnBindIpChanged(i)
+            pass Connected(connected::Message::OnBindIpChanged(i));
+
+        // OnConnecting
+        given OnConnecting ignore Locked;
+
+        given OnConnecting(server)
+            turn Disconnected(_) | ConnectionFailed(_)
+            into Connecting(connecting::State::new(server, None, None));
+
+        given OnConnecting ignore Connecting;
+        given OnConnecting ignore Connected;
+
         // OnConnectionFailed
+        given OnConnectionFailed ignore Locked;
         given OnConnectionFailed ignore Disconnected;
 
         given OnConnectionFailed(why)
-            turn Connecting(connecting::State {server, ..})
-            | Connected(connected::State {server, ..})
-            into ConnectionFailed(connection_failed::State::new(server, why));
+            turn Connecting(connecting::State {server, profile, ..})
+            | Connected(connected::State {server, profile, ..})
+            into ConnectionFailed(connection_failed::State::new(server, profile, why));
 
         given OnConnectionFailed ignore ConnectionFailed;
 
         // OnDisconnected
+        given OnDisconnected ignore Locked;
         given OnDisconnected ignore Disconnected;
         given OnDisconnected turn Connecting into Disconnected;
         given OnDisconnected ignore ConnectionFailed;
         given OnDisconnected turn Connected into Disconnected;
 
+        // OnFwdAddrChanged
+        given OnFwdAddrChanged ignore Locked;
+        given OnFwdAddrChanged ignore Disconnected;
+        given OnFwdAddrChanged ignore Connecting;
+        given OnFwdAddrChanged ignore ConnectionFailed;
+
+        given OnFwdAddrChanged(i)
+            pass Connected(connected::Message::OnFwdAddrChanged(i));
+
+        // OnFwdAddrResolved
+        given OnFwdAddrResolved ignore Locked;
+        given OnFwdAddrResolved ignore Disconnected;
+        given OnFwdAddrResolved ignore Connecting;
+        given OnFwdAddrResolved ignore ConnectionFailed;
+
+        given OnFwdAddrResolved(target, i)
+            pass Connected(connected::Message::OnFwdAddrResolved(target, i));
+
+        // OnFwdResolveFailed
+        given OnFwdResolveFailed ignore Locked;
+        given OnFwdResolveFailed ignore Disconnected;
+        given OnFwdResolveFailed ignore Connecting;
+        given OnFwdResolveFailed ignore ConnectionFailed;
+
+        given OnFwdResolveFailed(target, error)
+            pass Connected(connected::Message::OnFwdResolveFailed(target, error));
+
+        // OnPeerTraffic
+        given OnPeerTraffic ignore Locked;
+        given OnPeerTraffic ignore Disconnected;
+        given OnPeerTraffic ignore Connecting;
+        given OnPeerTraffic ignore ConnectionFailed;
+
+        given OnPeerTraffic(peer_addr, stats)
+            pass Connected(connected::Message::OnPeerTraffic(peer_addr, stats));
+
+        // OnPortRangeChanged
+        given OnPortRangeChanged ignore Locked;
+        given OnPortRangeChanged ignore Disconnected;
+        given OnPortRangeChanged ignore Connecting;
+        given OnPortRangeChanged ignore ConnectionFailed;
+
+        given OnPortRangeChanged(i)
+            pass Connected(connected::Message::OnPortRangeChanged(i));
+
         // OnRedirect
+        given OnRedirect ignore Locked;
         given OnRedirect ignore Disconnected;
 
         given OnRedirect(server)
-            turn Connecting(_)
-            into Disconnected(disconnected::State::new(format!("{server}")));
+            turn Connecting(i)
+            into Disconnected(disconnected::State::new(format!("{server}"), i.profile));
 
         given OnRedirect ignore ConnectionFailed;
         given OnRedirect ignore Connected;
 
+        // OnTrafficTick
+        given OnTrafficTick ignore Locked;
+        given OnTrafficTick ignore Disconnected;
+        given OnTrafficTick ignore Connecting;
+        given OnTrafficTick ignore ConnectionFailed;
+
+        given OnTrafficTick {}
+            pass Connected(connected::Message::OnTrafficTick);
+
         // ToConnecting
-        given ToConnecting { server, username, password}
+        given ToConnecting ignore Locked;
+
+        given ToConnecting { server, username, password, profile, fwd_addr }
             turn Disconnected(_)
-            into Connecting(connecting::State::new(server.clone()))
-            then (command_snd) {
-                command_snd
-                    .send(CommandMessage::ConnectRelay {
-                        server,
-                        username,
-                        password,
-                    })
+            into Connecting(connecting::State::new(server.clone(), profile, fwd_addr.clone()))
+            then ((relay_handle, storage)) {
+                if let Some(bind_ip) = storage.config.bind_ip {
+                    relay_handle
+                        .change_bind_ip(bind_ip)
+                        .unwrap();
+                }
+
+                if storage.config.port_range.is_some() {
+                    relay_handle
+                        .change_port_range(storage.config.port_range)
+                        .unwrap();
+                }
+
+                if let Some(max_attempts) = storage.config.retry_attempts {
+                    relay_handle
+                        .change_retry_policy(Some(RetryPolicy::new(max_attempts)))
+                        .unwrap();
+                }
+
+                if let Some(fwd_addr) = fwd_addr {
+                    relay_handle
+                        .change_fwd_target(fwd_addr)
+                        .unwrap();
+                }
+
+                relay_handle
+                    .connect_relay(RelayConfig::new(server, username, password))
                     .unwrap();
             };
 
@@ -113,6 +229,7 @@ router_component! {
         given ToConnecting ignore Connected;
 
         // ToDisconnected
+        given ToDisconnected turn Locked into Disconnected;
         given ToDisconnected ignore Disconnected;
         given ToDisconnected turn Connecting into Disconnected;
         given ToDisconnected turn ConnectionFailed into Disconnected;
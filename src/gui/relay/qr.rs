@@ -0,0 +1,197 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context};
+use iced::widget::{button, column, horizontal_space, qr_code, radio, row, text, vertical_space};
+use iced::{Element, Length, Task};
+
+use crate::gui::relay::connected;
+use crate::gui::types::IcedComponent;
+
+const MODULE_SIZE: usize = 8;
+const QUIET_ZONE: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Content {
+    RelayAddr,
+    Invite,
+}
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    Show(Content),
+    Export,
+    Close,
+}
+
+#[derive(Debug)]
+pub struct State {
+    relay_addr: SocketAddr,
+    invite: String,
+    content: Content,
+    data: Result<qr_code::Data, qr_code::Error>,
+    exported: Option<Result<PathBuf, String>>,
+}
+
+impl Clone for State {
+    fn clone(&self) -> Self {
+        Self {
+            relay_addr: self.relay_addr,
+            invite: self.invite.clone(),
+            content: self.content,
+            data: qr_code::Data::new(self.text()),
+            exported: self.exported.clone(),
+        }
+    }
+}
+
+impl State {
+    pub fn new(relay_addr: SocketAddr, invite: String) -> Self {
+        Self {
+            relay_addr,
+            data: qr_code::Data::new(&invite),
+            invite,
+            content: Content::Invite,
+            exported: None,
+        }
+    }
+
+    fn text(&self) -> String {
+        match self.content {
+            Content::RelayAddr => self.relay_addr.to_string(),
+            Content::Invite => self.invite.clone(),
+        }
+    }
+
+    fn export(&self) -> anyhow::Result<PathBuf> {
+        let dir = dirs::picture_dir()
+            .or_else(dirs::download_dir)
+            .or_else(dirs::home_dir)
+            .ok_or_else(|| anyhow!("Could not find the pictures directory"))?;
+
+        let path = dir.join(format!(
+            "turn_relay_{}_{}.png",
+            self.relay_addr.port(),
+            match self.content {
+                Content::RelayAddr => "address",
+                Content::Invite => "invite",
+            }
+        ));
+
+        write_png(&self.text(), &path)
+            .with_context(|| format!("Could not write {}", path.display()))?;
+
+        Ok(path)
+    }
+}
+
+/// Renders the same QR code as the widget, black on white, with a quiet zone
+/// around it.
+fn write_png(text: &str, path: &Path) -> anyhow::Result<()> {
+    let code = qrcode::QrCode::new(text)?;
+    let width = code.width();
+    let size = (width + QUIET_ZONE * 2) * MODULE_SIZE;
+    let mut pixels = vec![u8::MAX; size * size];
+
+    for (i, color) in code.to_colors().into_iter().enumerate() {
+        if color == qrcode::Color::Light {
+            continue;
+        }
+
+        let x = (i % width + QUIET_ZONE) * MODULE_SIZE;
+        let y = (i / width + QUIET_ZONE) * MODULE_SIZE;
+
+        for row in y..y + MODULE_SIZE {
+            pixels[row * size + x..][..MODULE_SIZE].fill(0);
+        }
+    }
+
+    let size = u32::try_from(size)?;
+    let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), size, size);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()?.write_image_data(&pixels)?;
+
+    Ok(())
+}
+
+impl IcedComponent for State {
+    type Message = Message;
+    type TaskMessage = super::Message;
+    type ExtraUpdateArgs<'a> = ();
+    type ExtraViewArgs<'a> = ();
+    type ExtraSubscriptionArgs<'a> = ();
+
+    fn update(
+        &mut self,
+        message: Self::Message,
+        _extra: Self::ExtraUpdateArgs<'_>,
+    ) -> Task<Self::TaskMessage> {
+        match message {
+            Message::Show(i) => {
+                self.content = i;
+                self.data = qr_code::Data::new(self.text());
+                self.exported = None;
+            }
+
+            Message::Export => {
+                self.exported = Some(self.export().map_err(|e| {
+                    eprintln!("Could not export the QR code: {e:#}");
+                    format!("{e:#}")
+                }));
+            }
+
+            Message::Close => {
+                return Task::done(connected::Message::ToggleQr.into());
+            }
+        }
+
+        Task::none()
+    }
+
+    fn view<'a>(&'a self, _extra: Self::ExtraViewArgs<'_>) -> Element<'a, Self::Message> {
+        row![
+            match &self.data {
+                Ok(data) => Element::from(qr_code(data).cell_size(6)),
+                Err(e) => text!("Could not make a QR code: {e}")
+                    .style(text::danger)
+                    .into(),
+            },
+            horizontal_space().width(16),
+            column![
+                radio(
+                    "Invite code",
+                    Content::Invite,
+                    Some(self.content),
+                    Message::Show
+                ),
+                vertical_space().height(8),
+                radio(
+                    "Relay address",
+                    Content::RelayAddr,
+                    Some(self.content),
+                    Message::Show
+                ),
+                vertical_space().height(16),
+                text!("{}", self.text()),
+                vertical_space().height(16),
+                row![
+                    button(text!("Export PNG"))
+                        .on_press_maybe(self.data.is_ok().then_some(Message::Export)),
+                    horizontal_space().width(8),
+                    button(text!("Close")).on_press(Message::Close),
+                ],
+                vertical_space().height(8),
+                match &self.exported {
+                    None => text!(""),
+                    Some(Ok(path)) => text!("Saved to {}", path.display()),
+                    Some(Err(e)) => text!("{e}").style(text::danger),
+                },
+            ]
+            .width(Length::Fill),
+        ]
+        .into()
+    }
+}
@@ -0,0 +1,333 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::net::SocketAddr;
+use std::ops::Add;
+use std::time::{Duration, Instant};
+
+use iced::widget::canvas::{Frame, Geometry, Path, Stroke};
+use iced::widget::{canvas, column, container, horizontal_space, row, text, tooltip};
+use iced::{mouse, Element, Length, Point, Rectangle, Renderer, Theme};
+
+use turn_relay_worker::TrafficStats;
+
+pub const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+/// Number of samples kept, i.e. three minutes.
+const HISTORY_LEN: usize = 180;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Unit {
+    Packets,
+    Bytes,
+}
+
+impl Unit {
+    fn format(self, value: u64) -> String {
+        match self {
+            Self::Packets => format!("{value} packets/s"),
+            Self::Bytes if value < 1000 => format!("{value} B/s"),
+            Self::Bytes if value < 1_000_000 => {
+                format!("{}.{} kB/s", value / 1000, value % 1000 / 100)
+            }
+            Self::Bytes => format!("{}.{} MB/s", value / 1_000_000, value % 1_000_000 / 100_000),
+        }
+    }
+}
+
+/// Traffic relayed during one sample interval.
+#[derive(Debug, Clone, Copy, Default)]
+struct Sample {
+    packets_in: u64,
+    bytes_in: u64,
+    packets_out: u64,
+    bytes_out: u64,
+}
+
+impl Sample {
+    fn between(prev: &TrafficStats, next: &TrafficStats) -> Self {
+        // The counters restart whenever the socket is bound again.
+        let prev = if next.packets_in < prev.packets_in
+            || next.bytes_in < prev.bytes_in
+            || next.packets_out < prev.packets_out
+            || next.bytes_out < prev.bytes_out
+        {
+            &TrafficStats::default()
+        } else {
+            prev
+        };
+
+        Self {
+            packets_in: next.packets_in.saturating_sub(prev.packets_in),
+            bytes_in: next.bytes_in.saturating_sub(prev.bytes_in),
+            packets_out: next.packets_out.saturating_sub(prev.packets_out),
+            bytes_out: next.bytes_out.saturating_sub(prev.bytes_out),
+        }
+    }
+
+    const fn get(&self, unit: Unit) -> (u64, u64) {
+        match unit {
+            Unit::Packets => (self.packets_in, self.packets_out),
+            Unit::Bytes => (self.bytes_in, self.bytes_out),
+        }
+    }
+}
+
+impl Add for Sample {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Self {
+            packets_in: self.packets_in + rhs.packets_in,
+            bytes_in: self.bytes_in + rhs.bytes_in,
+            packets_out: self.packets_out + rhs.packets_out,
+            bytes_out: self.bytes_out + rhs.bytes_out,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    samples: VecDeque<Sample>,
+}
+
+impl History {
+    fn push(&mut self, sample: Sample) {
+        if self.samples.len() >= HISTORY_LEN {
+            self.samples.pop_front();
+        }
+
+        self.samples.push_back(sample);
+    }
+
+    fn last(&self) -> Sample {
+        self.samples.back().copied().unwrap_or_default()
+    }
+
+    fn describe(&self) -> String {
+        let last = self.last();
+        let (packets_in, packets_out) = last.get(Unit::Packets);
+        let (bytes_in, bytes_out) = last.get(Unit::Bytes);
+
+        format!(
+            "In: {}, {}\nOut: {}, {}",
+            Unit::Bytes.format(bytes_in),
+            Unit::Packets.format(packets_in),
+            Unit::Bytes.format(bytes_out),
+            Unit::Packets.format(packets_out),
+        )
+    }
+
+    fn chart<Message: 'static>(
+        &self,
+        unit: Unit,
+        width: impl Into<Length>,
+        height: impl Into<Length>,
+    ) -> Element<'_, Message> {
+        canvas(Chart {
+            history: self,
+            unit,
+        })
+        .width(width)
+        .height(height)
+        .into()
+    }
+
+    /// Shows a small chart of the bytes per second, for a peer row.
+    pub fn view_sparkline<Message: 'static>(&self) -> Element<'_, Message> {
+        tooltip(
+            self.chart(Unit::Bytes, 64, 24),
+            container(text!("{}", self.describe()))
+                .padding(4)
+                .style(container::rounded_box),
+            tooltip::Position::Bottom,
+        )
+        .into()
+    }
+
+    /// Shows a chart of the bytes per second, and one of the packets per
+    /// second, for the whole relay.
+    pub fn view_charts<Message: 'static>(&self) -> Element<'_, Message> {
+        let (bytes_in, bytes_out) = self.last().get(Unit::Bytes);
+        let (packets_in, packets_out) = self.last().get(Unit::Packets);
+
+        column![
+            row![
+                text!("Traffic").width(96),
+                horizontal_space().width(8),
+                text!("In: {}", Unit::Bytes.format(bytes_in)).style(text::primary),
+                horizontal_space().width(8),
+                text!("Out: {}", Unit::Bytes.format(bytes_out)).style(text::success),
+                horizontal_space(),
+                text!("{}", Unit::Packets.format(packets_in)).style(text::primary),
+                horizontal_space().width(8),
+                text!("{}", Unit::Packets.format(packets_out)).style(text::success),
+            ],
+            row![
+                horizontal_space().width(96 + 8),
+                self.chart(Unit::Bytes, Length::Fill, 64),
+                horizontal_space().width(8),
+                self.chart(Unit::Packets, Length::Fill, 64),
+            ],
+        ]
+        .into()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct PeerTraffic {
+    stats: TrafficStats,
+    sampled: TrafficStats,
+    history: History,
+    last_seen: Option<Instant>,
+}
+
+impl PeerTraffic {
+    fn sample(&mut self) -> Sample {
+        let sample = Sample::between(&self.sampled, &self.stats);
+
+        self.sampled = self.stats;
+        self.history.push(sample);
+        sample
+    }
+}
+
+/// Traffic history of every peer, and of the whole relay.
+#[derive(Debug, Clone, Default)]
+pub struct Traffic {
+    peers: BTreeMap<SocketAddr, PeerTraffic>,
+    pub relay: History,
+}
+
+impl Traffic {
+    pub fn update(&mut self, peer_addr: SocketAddr, stats: TrafficStats) {
+        let peer = self.peers.entry(peer_addr).or_default();
+
+        // The stats are only reported when they change.
+        peer.stats = stats;
+        peer.last_seen = Some(Instant::now());
+    }
+
+    /// Takes a sample of every peer that is kept, and of the whole relay.
+    pub fn sample(&mut self, mut keep: impl FnMut(SocketAddr) -> bool) {
+        self.peers.retain(|peer_addr, _| keep(*peer_addr));
+
+        let total = self
+            .peers
+            .values_mut()
+            .map(PeerTraffic::sample)
+            .fold(Sample::default(), |a, b| a + b);
+
+        self.relay.push(total);
+    }
+
+    pub fn peer(&self, peer_addr: SocketAddr) -> Option<&History> {
+        self.peers.get(&peer_addr).map(|i| &i.history)
+    }
+
+    /// Bytes per second of a peer in both directions, as last sampled.
+    pub fn rate(&self, peer_addr: SocketAddr) -> u64 {
+        self.peer(peer_addr).map_or(0, |i| {
+            let (bytes_in, bytes_out) = i.last().get(Unit::Bytes);
+            bytes_in + bytes_out
+        })
+    }
+
+    /// When the traffic of a peer was last seen.
+    pub fn last_seen(&self, peer_addr: SocketAddr) -> Option<Instant> {
+        self.peers.get(&peer_addr).and_then(|i| i.last_seen)
+    }
+}
+
+struct Chart<'a> {
+    history: &'a History,
+    unit: Unit,
+}
+
+impl<Message> canvas::Program<Message> for Chart<'_> {
+    type State = ();
+
+    #[allow(clippy::cast_precision_loss)]
+    fn draw(
+        &self,
+        _state: &Self::State,
+        renderer: &Renderer,
+        theme: &Theme,
+        bounds: Rectangle,
+        _cursor: mouse::Cursor,
+    ) -> Vec<Geometry> {
+        let palette = theme.extended_palette();
+        let samples = &self.history.samples;
+        let mut frame = Frame::new(renderer, bounds.size());
+
+        frame.fill_rectangle(Point::ORIGIN, bounds.size(), palette.background.weak.color);
+
+        let max = samples
+            .iter()
+            .map(|i| {
+                let (a, b) = i.get(self.unit);
+                a.max(b)
+            })
+            .max()
+            .unwrap_or_default()
+            .max(1) as f32;
+
+        // The latest sample is at the right edge.
+        let offset = HISTORY_LEN - samples.len();
+        let step = bounds.width / (HISTORY_LEN - 1) as f32;
+        let height = bounds.height - 1.;
+
+        for (is_out, color) in [
+            (false, palette.primary.base.color),
+            (true, palette.success.base.color),
+        ] {
+            let path = Path::new(|b| {
+                for (index, sample) in samples.iter().enumerate() {
+                    let (value_in, value_out) = sample.get(self.unit);
+                    let value = if is_out { value_out } else { value_in };
+                    let point = Point::new(
+                        (offset + index) as f32 * step,
+                        (value as f32 / max).mul_add(-height, height) + 0.5,
+                    );
+
+                    if index == 0 {
+                        b.move_to(point);
+                    } else {
+                        b.line_to(point);
+                    }
+                }
+            });
+
+            frame.stroke(&path, Stroke::default().with_color(color).with_width(1.5));
+        }
+
+        vec![frame.into_geometry()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(packets: u64, bytes: u64) -> TrafficStats {
+        let mut stats = TrafficStats::default();
+        stats.packets_in = packets;
+        stats.bytes_in = bytes;
+        stats.packets_out = packets;
+        stats.bytes_out = bytes;
+        stats
+    }
+
+    #[test]
+    fn sample_counts_the_difference() {
+        let sample = Sample::between(&stats(2, 200), &stats(5, 700));
+
+        assert_eq!(sample.get(Unit::Packets), (3, 3));
+        assert_eq!(sample.get(Unit::Bytes), (500, 500));
+    }
+
+    #[test]
+    fn sample_restarts_when_any_counter_goes_down() {
+        let sample = Sample::between(&stats(2, 200), &stats(3, 100));
+
+        assert_eq!(sample.get(Unit::Packets), (3, 3));
+        assert_eq!(sample.get(Unit::Bytes), (100, 100));
+    }
+}
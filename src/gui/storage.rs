@@ -0,0 +1,80 @@
+use anyhow::{ensure, Context};
+
+use crate::config::Config;
+use crate::vault::Vault;
+
+#[derive(Debug, Default)]
+pub struct Storage {
+    pub config: Config,
+    pub vault: Option<Vault>,
+    /// Set when the configuration could not be loaded. Saving is refused
+    /// while it is set, so that the file is not replaced by the defaults.
+    pub load_error: Option<String>,
+}
+
+impl Storage {
+    pub fn load() -> Self {
+        match Config::load() {
+            Ok(config) => Self {
+                config,
+                ..Self::default()
+            },
+            Err(e) => {
+                eprintln!("Warning: Could not load the configuration: {e:#}");
+
+                Self {
+                    load_error: Some(format!("{e:#}")),
+                    ..Self::default()
+                }
+            }
+        }
+    }
+
+    pub fn save_config(&self) -> anyhow::Result<()> {
+        ensure!(
+            self.load_error.is_none(),
+            "Not saving the configuration, since it could not be loaded"
+        );
+
+        self.config
+            .save()
+            .context("Could not save the configuration")
+    }
+
+    /// Uses the unlocked vault, and moves the invite secret into it.
+    pub fn set_vault(&mut self, mut vault: Vault) -> anyhow::Result<()> {
+        let is_moved = vault.take_invite_secret(&mut self.config);
+        let result = if is_moved { vault.save() } else { Ok(()) };
+
+        // Keep the secret in the configuration until the vault is written, so
+        // a later save does not lose it.
+        if result.is_err() {
+            self.config.invite_secret = vault.invite_secret().map(str::to_owned);
+        }
+
+        self.vault = Some(vault);
+        result.context("Could not save the credentials")?;
+
+        if is_moved {
+            self.save_config()?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the invite secret of the vault, or else the one which is not
+    /// moved into it yet.
+    pub fn invite_secret(&self) -> Option<&str> {
+        self.vault
+            .as_ref()
+            .and_then(Vault::invite_secret)
+            .or(self.config.invite_secret.as_deref())
+    }
+
+    pub fn save_vault(&self) -> anyhow::Result<()> {
+        self.vault
+            .as_ref()
+            .map_or(Ok(()), Vault::save)
+            .context("Could not save the credentials")
+    }
+}
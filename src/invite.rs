@@ -0,0 +1,170 @@
+use std::fmt::Write;
+use std::net::SocketAddr;
+
+use anyhow::{anyhow, bail, ensure, Context};
+use blake2::digest::{FixedOutput, KeyInit, Update};
+use blake2::{Blake2b512, Blake2bMac512, Digest};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, NON_ALPHANUMERIC};
+use serde::Serialize;
+
+const SCHEME: &str = "turn-relay://";
+const CHECKSUM_LEN: usize = 4;
+const SIGNATURE_LEN: usize = 12;
+
+/// Address of a relay, shared as a `turn-relay://` URI instead of a bare
+/// address.
+///
+/// The code ends with a `sum` checksum to catch typos, or with a `sig`
+/// signature instead when both sides share a secret. Both cover every byte of
+/// the code before them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Invite {
+    pub relay_addr: SocketAddr,
+    pub label: Option<String>,
+    pub fwd_port: Option<u16>,
+}
+
+impl Invite {
+    pub fn encode(&self, secret: Option<&str>) -> String {
+        let mut code = format!("{SCHEME}{}", self.relay_addr);
+        let mut separator = '?';
+
+        if let Some(label) = &self.label {
+            let label = utf8_percent_encode(label, NON_ALPHANUMERIC);
+            write!(code, "{separator}label={label}").unwrap();
+            separator = '&';
+        }
+
+        if let Some(port) = self.fwd_port {
+            write!(code, "{separator}fwd={port}").unwrap();
+            separator = '&';
+        }
+
+        let (key, tag) = secret.map_or_else(
+            || ("sum", checksum(&code)),
+            |secret| ("sig", signature(&code, secret)),
+        );
+
+        write!(code, "{separator}{key}={tag}").unwrap();
+        code
+    }
+
+    pub fn decode(code: &str, secret: Option<&str>) -> anyhow::Result<Self> {
+        let code = code.trim();
+        let rest = code
+            .strip_prefix(SCHEME)
+            .ok_or_else(|| anyhow!("Invite codes start with {SCHEME}"))?;
+
+        let (payload, tag) = code
+            .rsplit_once(['?', '&'])
+            .ok_or_else(|| anyhow!("Invite code has no checksum"))?;
+
+        match (tag.split_once('='), secret) {
+            (Some(("sig", tag)), Some(secret)) => ensure!(
+                tag.eq_ignore_ascii_case(&signature(payload, secret)),
+                "Invite code was not signed with this secret"
+            ),
+            (Some(("sig", _)), None) => bail!("Invite code is signed, but no secret was given"),
+            (Some(("sum", _)), Some(_)) => bail!("Invite code is not signed"),
+            (Some(("sum", tag)), None) => ensure!(
+                tag.eq_ignore_ascii_case(&checksum(payload)),
+                "Invite code is mistyped or incomplete"
+            ),
+            _ => bail!("Invite code has no checksum"),
+        }
+
+        let (relay_addr, query) = rest.split_once('?').unwrap_or((rest, ""));
+        let mut invite = Self {
+            relay_addr: relay_addr.parse().context("Invalid relay address")?,
+            label: None,
+            fwd_port: None,
+        };
+
+        for (key, value) in query.split('&').filter_map(|i| i.split_once('=')) {
+            match key {
+                "label" => {
+                    invite.label = Some(
+                        percent_decode_str(value)
+                            .decode_utf8()
+                            .context("Invalid label")?
+                            .into_owned(),
+                    );
+                }
+                "fwd" => invite.fwd_port = Some(value.parse().context("Invalid forward port")?),
+                _ => {}
+            }
+        }
+
+        Ok(invite)
+    }
+}
+
+fn checksum(payload: &str) -> String {
+    to_hex(&Blake2b512::digest(payload)[..CHECKSUM_LEN])
+}
+
+fn signature(payload: &str, secret: &str) -> String {
+    let key = Blake2b512::digest(secret);
+    let mut mac = <Blake2bMac512 as KeyInit>::new_from_slice(&key).unwrap();
+    mac.update(payload.as_bytes());
+    to_hex(&mac.finalize_fixed()[..SIGNATURE_LEN])
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut s, i| {
+        write!(s, "{i:02x}").unwrap();
+        s
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invite() -> Invite {
+        Invite {
+            relay_addr: "203.0.113.7:49152".parse().unwrap(),
+            label: Some("Bob & Alice".to_string()),
+            fwd_port: Some(34197),
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let code = invite().encode(None);
+
+        assert!(code.contains("&sum="));
+        assert_eq!(Invite::decode(&code, None).unwrap(), invite());
+    }
+
+    #[test]
+    fn signed_round_trip() {
+        let code = invite().encode(Some("secret"));
+
+        assert!(code.contains("&sig="));
+        assert_eq!(Invite::decode(&code, Some("secret")).unwrap(), invite());
+    }
+
+    #[test]
+    fn checksum_mismatch_is_rejected() {
+        let code = invite().encode(None).replace("49152", "49153");
+
+        assert!(Invite::decode(&code, None).is_err());
+    }
+
+    #[test]
+    fn signature_mismatch_is_rejected() {
+        let code = invite().encode(Some("secret"));
+
+        assert!(Invite::decode(&code, Some("another secret")).is_err());
+        assert!(Invite::decode(&code, None).is_err());
+        assert!(Invite::decode(&invite().encode(None), Some("secret")).is_err());
+    }
+
+    #[test]
+    fn bad_scheme_is_rejected() {
+        let code = invite().encode(None).replace(SCHEME, "https://");
+
+        assert!(Invite::decode(&code, None).is_err());
+    }
+}
@@ -1,19 +1,20 @@
+mod cli;
+mod config;
+#[cfg(unix)]
+mod control;
 mod gui;
-mod macros;
-mod worker;
+mod invite;
+mod vault;
 
-use std::net::{IpAddr, SocketAddr};
+use clap::Parser;
 
-use crate::macros::addr;
-
-pub const ALL_IP: IpAddr = addr!(0, 0, 0, 0);
-pub const LOCAL_IP: IpAddr = addr!(127, 0, 0, 1);
-pub const ALL_DYN_SOCKET: SocketAddr = addr!(ALL_IP:0);
-pub const LOCAL_DYN_SOCKET: SocketAddr = addr!(LOCAL_IP:0);
-pub const DEFAULT_FWD_SOCKET: SocketAddr = addr!(LOCAL_IP:34197);
+pub const EVENT_CHANNEL_CAPACITY: usize = u8::MAX as usize;
 
 fn main() -> anyhow::Result<()> {
-    gui::run()?;
+    match cli::Args::parse().command {
+        Some(command) => cli::run(command)?,
+        None => gui::run()?,
+    }
 
     Ok(())
 }
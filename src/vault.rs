@@ -0,0 +1,221 @@
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::PathBuf;
+
+use anyhow::{anyhow, ensure, Context};
+use argon2::Argon2;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+
+const MAGIC: &[u8; 4] = b"TRV1";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+
+#[derive(Default, Serialize, Deserialize)]
+struct Secrets {
+    passwords: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    invite_secret: Option<String>,
+}
+
+/// Passwords of saved profiles and the invite secret, encrypted at rest with a
+/// key derived from a passphrase.
+///
+/// The file is laid out as the magic, the Argon2id salt, the XChaCha20-Poly1305
+/// nonce, then the encrypted JSON of the secrets.
+pub struct Vault {
+    key: Key,
+    salt: [u8; SALT_LEN],
+    secrets: Secrets,
+}
+
+impl Debug for Vault {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Vault({} passwords)", self.secrets.passwords.len())
+    }
+}
+
+impl Vault {
+    pub fn path() -> anyhow::Result<PathBuf> {
+        Ok(Config::dir()?.join("credentials.bin"))
+    }
+
+    pub fn exists() -> bool {
+        Self::path().is_ok_and(|i| i.exists())
+    }
+
+    fn derive_key(passphrase: &str, salt: &[u8]) -> anyhow::Result<Key> {
+        let mut key = Key::default();
+
+        Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+            .map_err(|e| anyhow!("Could not derive the vault key: {e}"))?;
+
+        Ok(key)
+    }
+
+    pub fn create(passphrase: &str) -> anyhow::Result<Self> {
+        let mut salt = [0; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+
+        Ok(Self {
+            key: Self::derive_key(passphrase, &salt)?,
+            salt,
+            secrets: Secrets::default(),
+        })
+    }
+
+    pub fn open(passphrase: &str) -> anyhow::Result<Self> {
+        let path = Self::path()?;
+        let data = fs::read(&path).with_context(|| format!("Could not read {}", path.display()))?;
+
+        Self::decrypt(passphrase, &data)
+            .with_context(|| format!("Could not open {}", path.display()))
+    }
+
+    fn decrypt(passphrase: &str, data: &[u8]) -> anyhow::Result<Self> {
+        ensure!(
+            data.len() > MAGIC.len() + SALT_LEN + NONCE_LEN && data.starts_with(MAGIC),
+            "Not a credentials file"
+        );
+
+        let (salt, data) = data[MAGIC.len()..].split_at(SALT_LEN);
+        let (nonce, data) = data.split_at(NONCE_LEN);
+        let key = Self::derive_key(passphrase, salt)?;
+
+        let plaintext = XChaCha20Poly1305::new(&key)
+            .decrypt(XNonce::from_slice(nonce), data)
+            .map_err(|_| anyhow!("Wrong passphrase, or the credentials file is corrupted"))?;
+
+        Ok(Self {
+            key,
+            salt: salt.try_into()?,
+            secrets: serde_json::from_slice(&plaintext)?,
+        })
+    }
+
+    pub fn open_or_create(passphrase: &str) -> anyhow::Result<Self> {
+        if Self::exists() {
+            Self::open(passphrase)
+        } else {
+            Self::create(passphrase)
+        }
+    }
+
+    fn encrypt(&self) -> anyhow::Result<Vec<u8>> {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+
+        let ciphertext = XChaCha20Poly1305::new(&self.key)
+            .encrypt(&nonce, serde_json::to_vec(&self.secrets)?.as_ref())
+            .map_err(|_| anyhow!("Could not encrypt the credentials"))?;
+
+        Ok([MAGIC.as_slice(), &self.salt, &nonce, &ciphertext].concat())
+    }
+
+    pub fn save(&self) -> anyhow::Result<()> {
+        let path = Self::path()?;
+        let temp_path = path.with_extension("bin.tmp");
+
+        fs::create_dir_all(Config::dir()?)?;
+
+        // A stale file from an interrupted save would keep its permissions.
+        match fs::remove_file(&temp_path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+
+        // The file is private from the start, rather than made so after the
+        // secrets are written into it.
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+
+        options.open(&temp_path)?.write_all(&self.encrypt()?)?;
+
+        fs::rename(&temp_path, &path)?;
+
+        Ok(())
+    }
+
+    pub fn password(&self, profile: &str) -> Option<&str> {
+        self.secrets.passwords.get(profile).map(String::as_str)
+    }
+
+    pub fn set_password(&mut self, profile: &str, password: String) {
+        self.secrets.passwords.insert(profile.to_string(), password);
+    }
+
+    pub fn remove_password(&mut self, profile: &str) {
+        self.secrets.passwords.remove(profile);
+    }
+
+    pub fn invite_secret(&self) -> Option<&str> {
+        self.secrets.invite_secret.as_deref()
+    }
+
+    /// Moves the invite secret written in the configuration into the vault,
+    /// and tells whether there was one. Both need to be saved afterwards.
+    pub fn take_invite_secret(&mut self, config: &mut Config) -> bool {
+        let Some(secret) = config.invite_secret.take() else {
+            return false;
+        };
+
+        self.secrets.invite_secret = Some(secret);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vault() -> Vault {
+        let mut vault = Vault::create("passphrase").unwrap();
+        let mut config = Config {
+            invite_secret: Some("secret".to_string()),
+            ..Config::default()
+        };
+
+        vault.set_password("home", "abc123".to_string());
+        assert!(vault.take_invite_secret(&mut config));
+        assert!(config.invite_secret.is_none());
+
+        vault
+    }
+
+    #[test]
+    fn round_trip() {
+        let data = vault().encrypt().unwrap();
+        let vault = Vault::decrypt("passphrase", &data).unwrap();
+
+        assert_eq!(vault.password("home"), Some("abc123"));
+        assert_eq!(vault.invite_secret(), Some("secret"));
+    }
+
+    #[test]
+    fn wrong_passphrase_is_rejected() {
+        let data = vault().encrypt().unwrap();
+
+        assert!(Vault::decrypt("another passphrase", &data).is_err());
+    }
+
+    #[test]
+    fn tampered_ciphertext_is_rejected() {
+        let mut data = vault().encrypt().unwrap();
+        *data.last_mut().unwrap() ^= 1;
+
+        assert!(Vault::decrypt("passphrase", &data).is_err());
+    }
+}
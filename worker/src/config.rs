@@ -0,0 +1,448 @@
+use std::fmt::{Debug, Display};
+use std::net::{IpAddr, SocketAddr};
+use std::num::ParseIntError;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+use std::time::Duration;
+
+use ipnet::{AddrParseError, IpNet};
+use serde::{Deserialize, Serialize};
+
+/// Credentials of a TURN server.
+#[derive(Clone, Deserialize)]
+#[non_exhaustive]
+pub struct RelayConfig {
+    /// TURN server, as `host` or `host:port`. The port defaults to 3478.
+    pub server: String,
+    /// TURN username.
+    pub username: String,
+    /// TURN password.
+    pub password: String,
+}
+
+impl RelayConfig {
+    /// Creates the credentials of a TURN server.
+    #[must_use]
+    pub fn new(
+        server: impl Into<String>,
+        username: impl Into<String>,
+        password: impl Into<String>,
+    ) -> Self {
+        Self {
+            server: server.into(),
+            username: username.into(),
+            password: password.into(),
+        }
+    }
+}
+
+impl Debug for RelayConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RelayConfig")
+            .field("server", &self.server)
+            .field("username", &self.username)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Where the socket of a peer sends the traffic of the peer to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum ReturnMode {
+    /// Send to the forward address. Fits hosting a server locally.
+    #[default]
+    Forward,
+    /// Send to the last local address that sent to the socket. Fits running a
+    /// client locally.
+    Learn,
+    /// Send to the first local address that sent to the socket, and drop the
+    /// traffic of any other address.
+    LearnLocked,
+}
+
+impl ReturnMode {
+    /// Every mode, in the order they should be presented.
+    pub const ALL: [Self; 3] = [Self::Forward, Self::Learn, Self::LearnLocked];
+}
+
+impl Display for ReturnMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Forward => "Forward",
+            Self::Learn => "Learn",
+            Self::LearnLocked => "Learn once",
+        })
+    }
+}
+
+/// Which local addresses may send traffic to the peer through its socket.
+///
+/// It is written as `any`, `fwd`, or a comma-separated list of networks, such
+/// as `192.168.1.0/24,10.0.0.5`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum SourcePolicy {
+    /// Any address.
+    #[default]
+    Any,
+    /// Only the forward address of the peer.
+    FwdAddr,
+    /// Only addresses in the given networks.
+    Allow(Vec<IpNet>),
+}
+
+impl SourcePolicy {
+    /// Whether a local address may send traffic to the peer.
+    #[must_use]
+    pub fn allows(&self, src: SocketAddr, fwd_addr: SocketAddr) -> bool {
+        match self {
+            Self::Any => true,
+            Self::FwdAddr => src == fwd_addr,
+            Self::Allow(i) => i.iter().any(|i| i.contains(&src.ip())),
+        }
+    }
+}
+
+impl Display for SourcePolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Any => f.write_str("any"),
+            Self::FwdAddr => f.write_str("fwd"),
+            Self::Allow(i) => {
+                for (index, i) in i.iter().enumerate() {
+                    if index > 0 {
+                        f.write_str(",")?;
+                    }
+
+                    write!(f, "{i}")?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+impl FromStr for SourcePolicy {
+    type Err = AddrParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "" | "any" => Ok(Self::Any),
+            "fwd" => Ok(Self::FwdAddr),
+            s => s
+                .split(',')
+                .map(|i| {
+                    let i = i.trim();
+                    i.parse::<IpAddr>()
+                        .map_or_else(|_| i.parse(), |i| Ok(i.into()))
+                })
+                .collect::<Result<_, _>>()
+                .map(Self::Allow),
+        }
+    }
+}
+
+/// Range of local ports to bind the sockets of unpinned peers on.
+///
+/// It is written as `start-end`, such as `40000-40099`, or as a single port.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "UncheckedPortRange")]
+#[non_exhaustive]
+pub struct PortRange {
+    /// First port of the range.
+    pub start: u16,
+    /// Last port of the range, inclusive.
+    pub end: u16,
+}
+
+impl PortRange {
+    /// Creates a range between two ports, in either order.
+    #[must_use]
+    pub fn new(start: u16, end: u16) -> Self {
+        Self {
+            start: start.min(end),
+            end: start.max(end),
+        }
+    }
+
+    /// Whether the port is in the range.
+    #[must_use]
+    pub const fn contains(&self, port: u16) -> bool {
+        self.start <= port && port <= self.end
+    }
+
+    /// Every port of the range, in order.
+    #[must_use]
+    pub const fn ports(&self) -> RangeInclusive<u16> {
+        self.start..=self.end
+    }
+}
+
+/// Port range as written, whose ends may be swapped.
+#[derive(Deserialize)]
+struct UncheckedPortRange {
+    start: u16,
+    end: u16,
+}
+
+impl From<UncheckedPortRange> for PortRange {
+    fn from(value: UncheckedPortRange) -> Self {
+        Self::new(value.start, value.end)
+    }
+}
+
+impl Display for PortRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-{}", self.start, self.end)
+    }
+}
+
+impl FromStr for PortRange {
+    type Err = ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, end) = s.split_once('-').unwrap_or((s, s));
+        Ok(Self::new(start.trim().parse()?, end.trim().parse()?))
+    }
+}
+
+/// When the socket of a peer is idle, after relaying no traffic in either
+/// direction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct IdlePolicy {
+    /// Seconds without traffic after which the peer is reported as idle.
+    pub idle_after: u64,
+    /// Seconds without traffic after which the peer is disconnected, as with
+    /// [`CommandMessage::DisconnectPeer`](crate::CommandMessage::DisconnectPeer).
+    /// It is never disconnected if it is not set.
+    #[serde(default)]
+    pub remove_after: Option<u64>,
+}
+
+impl IdlePolicy {
+    /// Creates a policy that reports peers as idle, and optionally
+    /// disconnects them later.
+    #[must_use]
+    pub const fn new(idle_after: u64, remove_after: Option<u64>) -> Self {
+        Self {
+            idle_after,
+            remove_after,
+        }
+    }
+}
+
+/// How peers are retried after their permission is denied, or their socket
+/// could not be bound.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
+#[non_exhaustive]
+pub struct RetryPolicy {
+    /// Number of retries before giving up on a peer.
+    pub max_attempts: u32,
+    /// Seconds to wait before the first retry. It doubles on every retry.
+    pub initial_delay: u64,
+    /// Most seconds to wait before a retry.
+    pub max_delay: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new(5)
+    }
+}
+
+impl RetryPolicy {
+    /// Creates a policy that retries a peer up to a number of times, waiting
+    /// from 2 seconds up to a minute in between.
+    #[must_use]
+    pub const fn new(max_attempts: u32) -> Self {
+        Self {
+            max_attempts,
+            initial_delay: 2,
+            max_delay: 60,
+        }
+    }
+
+    /// Time to wait before the given retry, counting from 1.
+    #[must_use]
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = 1u64
+            .checked_shl(attempt.saturating_sub(1))
+            .unwrap_or(u64::MAX);
+        Duration::from_secs(
+            self.initial_delay
+                .saturating_mul(factor)
+                .min(self.max_delay),
+        )
+    }
+}
+
+/// A peer to relay.
+#[derive(Debug, Clone, Deserialize)]
+#[non_exhaustive]
+pub struct PeerConfig {
+    /// Address of the peer, as seen by the TURN server.
+    pub peer_addr: SocketAddr,
+    /// Local address to bind the socket of the peer to. A dynamic port on the
+    /// address set by [`CommandMessage::ChangeBindIp`](crate::CommandMessage::ChangeBindIp)
+    /// is used if it is not set, which is the loopback interface by default.
+    #[serde(default)]
+    pub local_addr: Option<SocketAddr>,
+    /// Local address to forward the traffic of the peer to. The address set
+    /// by [`CommandMessage::ChangeFwdAddr`](crate::CommandMessage::ChangeFwdAddr)
+    /// is used if it is not set.
+    #[serde(default)]
+    pub fwd_addr: Option<SocketAddr>,
+    /// Where to send the traffic of the peer to.
+    #[serde(default)]
+    pub return_mode: ReturnMode,
+    /// Which local addresses may send traffic to the peer.
+    #[serde(default)]
+    pub source_policy: SourcePolicy,
+    /// Range of local ports to pick from if the port of the peer is not
+    /// pinned. The range set by
+    /// [`CommandMessage::ChangePortRange`](crate::CommandMessage::ChangePortRange)
+    /// is used if it is not set, and a dynamic port if neither is.
+    #[serde(default)]
+    pub port_range: Option<PortRange>,
+    /// Local port to try first if the port of the peer is not pinned, such as
+    /// the port of the peer in a previous session.
+    #[serde(default)]
+    pub preferred_port: Option<u16>,
+    /// Name of the peer, to show in the logs.
+    #[serde(default)]
+    pub label: Option<String>,
+    /// When the peer is idle. It is never idle if it is not set.
+    #[serde(default)]
+    pub idle_policy: Option<IdlePolicy>,
+}
+
+impl PeerConfig {
+    /// Creates a peer bound to a dynamic local port.
+    #[must_use]
+    pub const fn new(peer_addr: SocketAddr) -> Self {
+        Self {
+            peer_addr,
+            local_addr: None,
+            fwd_addr: None,
+            return_mode: ReturnMode::Forward,
+            source_policy: SourcePolicy::Any,
+            port_range: None,
+            preferred_port: None,
+            label: None,
+            idle_policy: None,
+        }
+    }
+
+    /// Pins the socket of the peer to a local address.
+    #[must_use]
+    pub const fn with_local_addr(mut self, local_addr: Option<SocketAddr>) -> Self {
+        self.local_addr = local_addr;
+        self
+    }
+
+    /// Forwards the traffic of the peer to a local address, instead of the
+    /// shared one.
+    #[must_use]
+    pub const fn with_fwd_addr(mut self, fwd_addr: Option<SocketAddr>) -> Self {
+        self.fwd_addr = fwd_addr;
+        self
+    }
+
+    /// Changes where the traffic of the peer is sent to.
+    #[must_use]
+    pub const fn with_return_mode(mut self, return_mode: ReturnMode) -> Self {
+        self.return_mode = return_mode;
+        self
+    }
+
+    /// Changes which local addresses may send traffic to the peer.
+    #[must_use]
+    pub fn with_source_policy(mut self, source_policy: SourcePolicy) -> Self {
+        self.source_policy = source_policy;
+        self
+    }
+
+    /// Picks the local port of the peer from a range, instead of the shared
+    /// one.
+    #[must_use]
+    pub const fn with_port_range(mut self, port_range: Option<PortRange>) -> Self {
+        self.port_range = port_range;
+        self
+    }
+
+    /// Tries a local port first, if the port of the peer is not pinned.
+    #[must_use]
+    pub const fn with_preferred_port(mut self, preferred_port: Option<u16>) -> Self {
+        self.preferred_port = preferred_port;
+        self
+    }
+
+    /// Names the peer in the logs.
+    #[must_use]
+    pub fn with_label(mut self, label: Option<String>) -> Self {
+        self.label = label;
+        self
+    }
+
+    /// Reports the peer as idle, and optionally disconnects it, after a while
+    /// without traffic.
+    #[must_use]
+    pub const fn with_idle_policy(mut self, idle_policy: Option<IdlePolicy>) -> Self {
+        self.idle_policy = idle_policy;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn port_range_from_str() {
+        assert_eq!("40000-40099".parse(), Ok(PortRange::new(40000, 40099)));
+        assert_eq!(" 40099 - 40000 ".parse(), Ok(PortRange::new(40000, 40099)));
+        assert_eq!("40000".parse(), Ok(PortRange::new(40000, 40000)));
+        assert!("".parse::<PortRange>().is_err());
+        assert!("40000-".parse::<PortRange>().is_err());
+        assert!("40000-70000".parse::<PortRange>().is_err());
+    }
+
+    #[test]
+    fn port_range_is_deserialized_in_order() {
+        let range: PortRange = serde_json::from_str(r#"{"start":40099,"end":40000}"#).unwrap();
+
+        assert_eq!(range, PortRange::new(40000, 40099));
+        assert_eq!((range.start, range.end), (40000, 40099));
+    }
+
+    #[test]
+    fn source_policy_from_str() {
+        assert_eq!("".parse(), Ok(SourcePolicy::Any));
+        assert_eq!("any".parse(), Ok(SourcePolicy::Any));
+        assert_eq!(" fwd ".parse(), Ok(SourcePolicy::FwdAddr));
+        assert_eq!(
+            "192.168.1.0/24, 10.0.0.5".parse(),
+            Ok(SourcePolicy::Allow(vec![
+                "192.168.1.0/24".parse().unwrap(),
+                "10.0.0.5/32".parse().unwrap(),
+            ]))
+        );
+        assert!("192.168.1.0/33".parse::<SourcePolicy>().is_err());
+        assert!("localhost".parse::<SourcePolicy>().is_err());
+    }
+
+    #[test]
+    fn retry_delay_doubles_up_to_the_max() {
+        let policy = RetryPolicy::new(5);
+        let delays: Vec<_> = (1..=7).map(|i| policy.delay(i).as_secs()).collect();
+
+        assert_eq!(delays, [2, 4, 8, 16, 32, 60, 60]);
+        assert_eq!(policy.delay(0), Duration::from_secs(2));
+        assert_eq!(policy.delay(u32::MAX).as_secs(), 60);
+    }
+}
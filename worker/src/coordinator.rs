@@ -0,0 +1,652 @@
+use std::mem::take;
+use std::sync::Arc;
+use std::time::Duration;
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    net::{IpAddr, SocketAddr},
+};
+
+use crate::log::{LogEntry, LogLevel};
+use crate::types::{CommandMessage, ServiceMessage};
+use crate::{addr, PeerConfig, PortRange, RetryPolicy, DEFAULT_FWD_SOCKET, LOCAL_IP};
+use futures::channel::mpsc;
+use futures::future::join_all;
+use futures::{SinkExt, StreamExt};
+use tokio::net::lookup_host;
+use tokio::select;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::sync::{watch, Notify};
+use tokio::task::JoinHandle;
+use tokio::time::{interval, sleep, Interval, MissedTickBehavior};
+
+use crate::types::{DataMessage, OutcomeMessage};
+use crate::types::{
+    ToAnyhowResult, ToWorkerErr, WorkerErr, WorkerOk, WorkerResult, WorkerResultHelper,
+};
+use crate::{peer, relay};
+
+pub const DATA_CHANNEL_CAPACITY: usize = u8::MAX as usize;
+pub const SERVICE_CHANNEL_CAPACITY: usize = u8::MAX as usize;
+pub const COMMAND_CHANNEL_CAPACITY: usize = u8::MAX as usize;
+pub const FWD_RESOLVE_INTERVAL: Duration = Duration::from_mins(1);
+
+type ResolveMessage = (String, io::Result<SocketAddr>);
+
+pub struct Worker {
+    command_snd: broadcast::Sender<CommandMessage>,
+    command_rcv: broadcast::Receiver<CommandMessage>,
+    service_snd: mpsc::Sender<ServiceMessage>,
+    upstream_snd: mpsc::Sender<DataMessage>,
+    downstream_snd: broadcast::Sender<DataMessage>,
+    relay: JoinHandle<()>,
+    peers: HashMap<String, JoinHandle<()>>,
+    fwd_snd: watch::Sender<SocketAddr>,
+    fwd_target: Option<String>,
+    is_resolving: bool,
+    resolve_snd: mpsc::Sender<ResolveMessage>,
+    resolve_rcv: mpsc::Receiver<ResolveMessage>,
+    resolve_notify: Arc<Notify>,
+    resolve_interval: Interval,
+    bind_ip: IpAddr,
+    port_range: Option<PortRange>,
+    retry_policy: Option<RetryPolicy>,
+    /// Peers as they were requested, to connect them again.
+    peer_configs: HashMap<SocketAddr, PeerConfig>,
+    /// Number of retries of failed peers, and the pending retry.
+    retries: HashMap<SocketAddr, (u32, JoinHandle<()>)>,
+    /// Peers being connected again by a retry, rather than by the user.
+    retrying: HashSet<SocketAddr>,
+    retry_snd: mpsc::Sender<SocketAddr>,
+    retry_rcv: mpsc::Receiver<SocketAddr>,
+    outcome_snd: mpsc::Sender<OutcomeMessage>,
+    outcome_rcv: mpsc::Receiver<OutcomeMessage>,
+}
+
+impl Worker {
+    pub fn new(
+        command_snd: broadcast::Sender<CommandMessage>,
+        service_snd: mpsc::Sender<ServiceMessage>,
+    ) -> Self {
+        let command_rcv = command_snd.subscribe();
+
+        let (upstream_snd, upstream_rcv) = mpsc::channel::<DataMessage>(DATA_CHANNEL_CAPACITY);
+        let (downstream_snd, _) = broadcast::channel::<DataMessage>(DATA_CHANNEL_CAPACITY);
+        let (outcome_snd, outcome_rcv) = mpsc::channel(SERVICE_CHANNEL_CAPACITY);
+        let (retry_snd, retry_rcv) = mpsc::channel(SERVICE_CHANNEL_CAPACITY);
+
+        let relay = tokio::spawn(
+            relay::Worker::new(
+                upstream_rcv,
+                downstream_snd.clone(),
+                command_snd.subscribe(),
+                service_snd.clone(),
+                outcome_snd.clone(),
+            )
+            .start(),
+        );
+
+        let (resolve_snd, resolve_rcv) = mpsc::channel(1);
+        let mut resolve_interval = interval(FWD_RESOLVE_INTERVAL);
+        resolve_interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        Self {
+            command_snd,
+            command_rcv,
+            service_snd,
+            upstream_snd,
+            downstream_snd,
+            relay,
+            peers: HashMap::new(),
+            fwd_snd: watch::Sender::new(DEFAULT_FWD_SOCKET),
+            fwd_target: None,
+            is_resolving: false,
+            resolve_snd,
+            resolve_rcv,
+            resolve_notify: Arc::new(Notify::new()),
+            resolve_interval,
+            bind_ip: LOCAL_IP,
+            port_range: None,
+            retry_policy: None,
+            peer_configs: HashMap::new(),
+            retries: HashMap::new(),
+            retrying: HashSet::new(),
+            retry_snd,
+            retry_rcv,
+            outcome_snd,
+            outcome_rcv,
+        }
+    }
+
+    fn log(&mut self, level: LogLevel, text: impl Into<String>) {
+        LogEntry::new(level, "Coordinator", None, text).report(&mut self.service_snd);
+    }
+
+    fn log_peer(&mut self, level: LogLevel, peer_addr: SocketAddr, text: impl Into<String>) {
+        LogEntry::new(level, "Coordinator", Some(peer_addr), text).report(&mut self.service_snd);
+    }
+
+    async fn change_fwd_addr(&mut self, fwd_addr: SocketAddr) -> anyhow::Result<()> {
+        self.log(LogLevel::Info, format!("Peers will forward to {fwd_addr}"));
+        self.fwd_target = None;
+        self.fwd_snd.send_replace(fwd_addr);
+
+        self.service_snd
+            .send(ServiceMessage::FwdAddrChanged(fwd_addr))
+            .await?;
+
+        Ok(())
+    }
+
+    /// Resolves the forward target in the background, unless it is already
+    /// being resolved.
+    fn resolve_fwd_target(&mut self) {
+        let Some(target) = self.fwd_target.clone() else {
+            return;
+        };
+
+        if self.is_resolving {
+            return;
+        }
+
+        self.is_resolving = true;
+        let mut resolve_snd = self.resolve_snd.clone();
+
+        tokio::spawn(async move {
+            let result = lookup_host(&target).await.and_then(|i| {
+                let addrs: Vec<_> = i.collect();
+
+                addrs
+                    .iter()
+                    .find(|i| i.is_ipv4())
+                    .or_else(|| addrs.first())
+                    .copied()
+                    .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No address found"))
+            });
+
+            let _ = resolve_snd.send((target, result)).await;
+        });
+    }
+
+    async fn handle_resolve_message(&mut self, (target, result): ResolveMessage) -> WorkerResult {
+        self.is_resolving = false;
+
+        // The target may have been changed while it was being resolved.
+        if self.fwd_target.as_ref() != Some(&target) {
+            self.resolve_fwd_target();
+            return WorkerResult::continued();
+        }
+
+        match result {
+            Ok(fwd_addr) => {
+                if !self.fwd_snd.send_if_modified(|i| {
+                    let is_modified = *i != fwd_addr;
+                    *i = fwd_addr;
+                    is_modified
+                }) {
+                    return WorkerResult::continued();
+                }
+
+                self.log(
+                    LogLevel::Info,
+                    format!("Peers will forward to {target} at {fwd_addr}"),
+                );
+
+                self.service_snd
+                    .send(ServiceMessage::FwdAddrResolved { target, fwd_addr })
+                    .await
+                    .anyhow()
+                    .into_recoverable()?;
+            }
+
+            Err(error) => {
+                self.log(
+                    LogLevel::Warning,
+                    format!("Could not resolve forward target {target}: {error}"),
+                );
+
+                self.service_snd
+                    .send(ServiceMessage::FwdResolveFailed {
+                        target,
+                        error: error.to_string(),
+                    })
+                    .await
+                    .anyhow()
+                    .into_recoverable()?;
+            }
+        }
+
+        WorkerResult::continued()
+    }
+
+    fn connect_peer(&mut self, mut config: PeerConfig) {
+        self.peer_configs.insert(config.peer_addr, config.clone());
+
+        // A retry keeps counting the attempts, while connecting the peer by
+        // hand starts over.
+        if !self.retrying.remove(&config.peer_addr) {
+            self.cancel_retry(config.peer_addr);
+        }
+
+        // A peer whose permission was denied keeps its socket, and reports it
+        // again.
+        if self
+            .peers
+            .get(&config.peer_addr.to_string())
+            .is_some_and(|i| !i.is_finished())
+        {
+            self.log_peer(
+                LogLevel::Info,
+                config.peer_addr,
+                format!("Peer {} is already bound", config.peer_addr),
+            );
+
+            return;
+        }
+
+        config
+            .local_addr
+            .get_or_insert_with(|| addr!((self.bind_ip):0));
+
+        if config.port_range.is_none() {
+            config.port_range = self.port_range;
+        }
+
+        self.peers.insert(
+            config.peer_addr.to_string(),
+            tokio::spawn(
+                peer::Worker::new(
+                    config,
+                    self.fwd_snd.subscribe(),
+                    self.resolve_notify.clone(),
+                    self.upstream_snd.clone(),
+                    self.downstream_snd.subscribe(),
+                    self.command_snd.clone(),
+                    self.service_snd.clone(),
+                    self.outcome_snd.clone(),
+                )
+                .start(),
+            ),
+        );
+    }
+
+    /// Connects a failed peer again after a while, unless it is retried too
+    /// many times already.
+    fn retry_peer(&mut self, peer_addr: SocketAddr) {
+        let Some(retry_policy) = self
+            .retry_policy
+            .filter(|_| self.peer_configs.contains_key(&peer_addr))
+        else {
+            return;
+        };
+
+        let attempt = self.cancel_retry(peer_addr) + 1;
+
+        if attempt > retry_policy.max_attempts {
+            self.log_peer(
+                LogLevel::Warning,
+                peer_addr,
+                format!(
+                    "Giving up on peer {peer_addr} after {} retries",
+                    attempt - 1
+                ),
+            );
+
+            return;
+        }
+
+        let delay = retry_policy.delay(attempt);
+
+        self.log_peer(
+            LogLevel::Info,
+            peer_addr,
+            format!("Retrying peer {peer_addr} in {} seconds", delay.as_secs()),
+        );
+
+        let mut retry_snd = self.retry_snd.clone();
+
+        let task = tokio::spawn(async move {
+            sleep(delay).await;
+            let _ = retry_snd.send(peer_addr).await;
+        });
+
+        self.retries.insert(peer_addr, (attempt, task));
+    }
+
+    /// Connects a peer again once its retry is due.
+    async fn handle_retry_message(&mut self, peer_addr: SocketAddr) -> WorkerResult {
+        let (Some((attempt, _)), Some(config)) = (
+            self.retries.get(&peer_addr),
+            self.peer_configs.get(&peer_addr),
+        ) else {
+            return WorkerResult::continued();
+        };
+
+        let attempt = *attempt;
+        let config = config.clone();
+
+        self.retrying.insert(peer_addr);
+        self.command_snd
+            .send(CommandMessage::ConnectPeer(config))
+            .anyhow()
+            .into_recoverable()?;
+
+        self.service_snd
+            .send(ServiceMessage::PeerRetrying { peer_addr, attempt })
+            .await
+            .anyhow()
+            .into_recoverable()?;
+
+        WorkerResult::continued()
+    }
+
+    /// Cancels the pending retry of a peer, and returns the number of its
+    /// retries so far.
+    fn cancel_retry(&mut self, peer_addr: SocketAddr) -> u32 {
+        self.retries
+            .remove(&peer_addr)
+            .map_or(0, |(attempt, task)| {
+                task.abort();
+                attempt
+            })
+    }
+
+    /// Forgets how a peer was connected, so that it is not retried.
+    fn forget_peer(&mut self, peer_addr: SocketAddr) {
+        self.peer_configs.remove(&peer_addr);
+        self.retrying.remove(&peer_addr);
+        self.cancel_retry(peer_addr);
+    }
+
+    fn forget_peers(&mut self) {
+        self.peer_configs.clear();
+        self.retrying.clear();
+
+        for (_, task) in take(&mut self.retries).into_values() {
+            task.abort();
+        }
+    }
+
+    fn handle_outcome_message(&mut self, outcome_message: OutcomeMessage) -> WorkerResult {
+        match outcome_message {
+            OutcomeMessage::Connected(peer_addr) => {
+                self.cancel_retry(peer_addr);
+            }
+
+            OutcomeMessage::Failed(peer_addr) => {
+                self.retry_peer(peer_addr);
+            }
+
+            OutcomeMessage::Rebound {
+                peer_addr,
+                local_addr,
+            } => {
+                if let Some(config) = self.peer_configs.get_mut(&peer_addr) {
+                    config.local_addr = Some(local_addr);
+                }
+            }
+        }
+
+        WorkerResult::continued()
+    }
+
+    async fn handle_command_message(
+        &mut self,
+        command_message: Result<CommandMessage, RecvError>,
+    ) -> WorkerResult {
+        match command_message.anyhow().into_recoverable()? {
+            CommandMessage::ConnectRelay(_)
+            | CommandMessage::PausePeer { .. }
+            | CommandMessage::RebindPeer { .. } => WorkerResult::continued(),
+
+            CommandMessage::ConnectPeer(config) => {
+                self.connect_peer(config);
+
+                WorkerResult::continued()
+            }
+
+            CommandMessage::ChangeFwdAddr(i) => {
+                self.change_fwd_addr(i).await.into_recoverable()?;
+
+                WorkerResult::continued()
+            }
+
+            CommandMessage::ChangeFwdTarget(target) => {
+                if let Ok(i) = target.parse() {
+                    self.change_fwd_addr(i).await.into_recoverable()?;
+                } else {
+                    self.log(LogLevel::Info, format!("Resolving forward target {target}"));
+                    self.fwd_target = Some(target);
+                    self.resolve_fwd_target();
+                }
+
+                WorkerResult::continued()
+            }
+
+            CommandMessage::ChangeBindIp(i) => {
+                self.log(LogLevel::Info, format!("New peers will bind on {i}"));
+                self.bind_ip = i;
+
+                self.service_snd
+                    .send(ServiceMessage::BindIpChanged(i))
+                    .await
+                    .anyhow()
+                    .into_recoverable()?;
+
+                WorkerResult::continued()
+            }
+
+            CommandMessage::ChangePortRange(i) => {
+                self.log(
+                    LogLevel::Info,
+                    i.map_or_else(
+                        || "New peers will bind on dynamic ports".to_string(),
+                        |i| format!("New peers will bind on ports {i}"),
+                    ),
+                );
+
+                self.port_range = i;
+
+                self.service_snd
+                    .send(ServiceMessage::PortRangeChanged(i))
+                    .await
+                    .anyhow()
+                    .into_recoverable()?;
+
+                WorkerResult::continued()
+            }
+
+            CommandMessage::ChangeRetryPolicy(i) => {
+                self.log(
+                    LogLevel::Info,
+                    i.map_or_else(
+                        || "Failed peers will not be retried".to_string(),
+                        |i| format!("Failed peers will be retried {} times", i.max_attempts),
+                    ),
+                );
+
+                self.retry_policy = i;
+
+                WorkerResult::continued()
+            }
+
+            CommandMessage::DisconnectAll => {
+                self.log(LogLevel::Info, "Disconnecting everything");
+
+                self.forget_peers();
+
+                let peers = take(&mut self.peers);
+
+                join_all(peers.into_values())
+                    .await
+                    .into_iter()
+                    .collect::<Result<Vec<()>, _>>()
+                    .anyhow()
+                    .into_recoverable()?;
+
+                WorkerResult::continued()
+            }
+
+            CommandMessage::DisconnectPeer(peer_addr) => {
+                self.forget_peer(peer_addr);
+
+                if let Some(peer) = self.peers.remove(&peer_addr.to_string()) {
+                    peer.await.anyhow().into_recoverable()?;
+                } else {
+                    self.log_peer(
+                        LogLevel::Warning,
+                        peer_addr,
+                        format!("Could not find peer {peer_addr} to disconnect"),
+                    );
+
+                    self.service_snd
+                        .send(ServiceMessage::PeerUnbound(peer_addr))
+                        .await
+                        .anyhow()
+                        .into_recoverable()?;
+                }
+
+                WorkerResult::continued()
+            }
+
+            CommandMessage::TerminateAll => {
+                self.log(LogLevel::Info, "Terminating");
+
+                WorkerResult::terminate()
+            }
+        }
+    }
+
+    async fn handle_loop(&mut self) -> WorkerResult {
+        select! {
+            command_message = self.command_rcv.recv() => {
+                self.handle_command_message(command_message).await
+            }
+            Some(outcome_message) = self.outcome_rcv.next() => {
+                self.handle_outcome_message(outcome_message)
+            }
+            Some(peer_addr) = self.retry_rcv.next() => {
+                self.handle_retry_message(peer_addr).await
+            }
+            Some(resolve_message) = self.resolve_rcv.next() => {
+                self.handle_resolve_message(resolve_message).await
+            }
+            () = self.resolve_notify.notified() => {
+                self.resolve_fwd_target();
+                WorkerResult::continued()
+            }
+            _ = self.resolve_interval.tick() => {
+                self.resolve_fwd_target();
+                WorkerResult::continued()
+            }
+        }
+    }
+
+    pub async fn start(mut self) {
+        self.log(LogLevel::Info, "Worker started");
+
+        loop {
+            match self.handle_loop().await {
+                Ok(WorkerOk::Continue) => {}
+                Ok(WorkerOk::Terminate) => break,
+                Err(WorkerErr::RecoverableError(error)) => {
+                    self.log(LogLevel::Error, format!("{error:#}"));
+                }
+                Err(WorkerErr::UnrecoverableError(error)) => {
+                    self.log(LogLevel::Fatal, format!("{error:#}"));
+                    break;
+                }
+            }
+        }
+
+        (&mut self.relay).await.unwrap();
+
+        let peers: HashMap<String, JoinHandle<()>> = take(&mut self.peers);
+
+        join_all(peers.into_values())
+            .await
+            .into_iter()
+            .collect::<Result<Vec<()>, _>>()
+            .unwrap();
+
+        self.log(LogLevel::Info, "Worker stopped");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PEER_ADDR: SocketAddr = addr!(192, 0, 2, 1:5678);
+
+    fn worker() -> (Worker, mpsc::Receiver<ServiceMessage>) {
+        let (service_snd, service_rcv) = mpsc::channel(SERVICE_CHANNEL_CAPACITY);
+        let mut worker = Worker::new(broadcast::Sender::new(16), service_snd);
+
+        worker.retry_policy = Some(RetryPolicy::new(3));
+        worker
+            .peer_configs
+            .insert(PEER_ADDR, PeerConfig::new(PEER_ADDR));
+
+        (worker, service_rcv)
+    }
+
+    #[tokio::test]
+    async fn delete_during_backoff_cancels_retry() {
+        let (mut worker, _service_rcv) = worker();
+
+        worker.retry_peer(PEER_ADDR);
+        assert_eq!(worker.retries.get(&PEER_ADDR).map(|i| i.0), Some(1));
+
+        worker
+            .handle_command_message(Ok(CommandMessage::DisconnectPeer(PEER_ADDR)))
+            .await
+            .unwrap();
+
+        assert!(worker.retries.is_empty());
+        assert!(worker.peer_configs.is_empty());
+
+        // A retry that was already due does not bring the peer back.
+        worker.handle_retry_message(PEER_ADDR).await.unwrap();
+        assert!(worker.retrying.is_empty());
+    }
+
+    #[tokio::test]
+    async fn manual_retry_during_backoff_cancels_retry() {
+        let (mut worker, _service_rcv) = worker();
+
+        worker.retry_peer(PEER_ADDR);
+        worker.connect_peer(PeerConfig::new(PEER_ADDR));
+
+        assert!(worker.retries.is_empty());
+    }
+
+    #[tokio::test]
+    async fn retry_keeps_counting_attempts() {
+        let (mut worker, _service_rcv) = worker();
+
+        worker.retry_peer(PEER_ADDR);
+        worker.handle_retry_message(PEER_ADDR).await.unwrap();
+        worker.connect_peer(PeerConfig::new(PEER_ADDR));
+
+        assert_eq!(worker.retries.get(&PEER_ADDR).map(|i| i.0), Some(1));
+
+        worker.retry_peer(PEER_ADDR);
+        assert_eq!(worker.retries.get(&PEER_ADDR).map(|i| i.0), Some(2));
+    }
+
+    #[tokio::test]
+    async fn retry_keeps_the_rebound_address() {
+        let (mut worker, _service_rcv) = worker();
+        let local_addr = addr!(127, 0, 0, 1:12345);
+
+        worker
+            .handle_outcome_message(OutcomeMessage::Rebound {
+                peer_addr: PEER_ADDR,
+                local_addr,
+            })
+            .unwrap();
+
+        assert_eq!(worker.peer_configs[&PEER_ADDR].local_addr, Some(local_addr));
+    }
+}
@@ -0,0 +1,250 @@
+use std::io;
+
+use serde::Serialize;
+use thiserror::Error;
+
+/// Why the connection to the TURN server failed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Error)]
+#[serde(tag = "kind", content = "detail", rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum RelayError {
+    /// The server could not be resolved.
+    #[error("Could not resolve {0}")]
+    Dns(String),
+    /// The server did not respond in time.
+    #[error("Server did not respond")]
+    Timeout,
+    /// The server rejected the credentials (401 or 438).
+    #[error("Server rejected the username or password")]
+    Unauthorized,
+    /// The server refused to allocate for this user (403).
+    #[error("Server refused to allocate a relay")]
+    Forbidden,
+    /// The user has too many allocations on the server (486).
+    #[error("Allocation quota is reached")]
+    QuotaReached,
+    /// The server failed with another error code.
+    #[error("Server failed with error {0}")]
+    Server(u16),
+    /// The local socket is already in use.
+    #[error("Address is already in use")]
+    AddrInUse,
+    /// The local socket could not be opened due to missing privileges.
+    #[error("Permission denied")]
+    PermissionDenied,
+    /// Any other error.
+    #[error("{0}")]
+    Other(String),
+}
+
+impl RelayError {
+    pub(crate) fn classify(error: &anyhow::Error) -> Self {
+        if let Some(e) = error.downcast_ref::<Self>() {
+            return e.clone();
+        }
+
+        if let Some(e) = error.downcast_ref::<io::Error>() {
+            match e.kind() {
+                io::ErrorKind::AddrInUse => return Self::AddrInUse,
+                io::ErrorKind::PermissionDenied => return Self::PermissionDenied,
+                io::ErrorKind::TimedOut => return Self::Timeout,
+                _ => {}
+            }
+        }
+
+        // turnclient reports everything as formatted strings.
+        let message = error.to_string();
+
+        if message == "Request timed out" {
+            return Self::Timeout;
+        }
+
+        if message == "Authentication failed" {
+            return Self::Unauthorized;
+        }
+
+        let code = message
+            .strip_prefix("Unknown error code from TURN: ")
+            .or_else(|| message.strip_prefix("Error from TURN: "))
+            .and_then(|i| i.parse().ok());
+
+        match code {
+            Some(401 | 438) => Self::Unauthorized,
+            Some(403) => Self::Forbidden,
+            Some(486) => Self::QuotaReached,
+            Some(i) => Self::Server(i),
+            None => Self::Other(format!("{error:#}")),
+        }
+    }
+
+    /// Suggests how the user may fix the error.
+    #[must_use]
+    pub const fn hint(&self) -> &'static str {
+        match self {
+            Self::Dns(_) => "Check the spelling of the server, and your internet connection.",
+            Self::Timeout => {
+                "Check the server address and port, and whether a firewall blocks UDP."
+            }
+            Self::Unauthorized => {
+                "Check the username and password. Some credentials expire after a while."
+            }
+            Self::Forbidden => "This user is not allowed to use the server.",
+            Self::QuotaReached => {
+                "Disconnect other clients using this user, or wait for their allocations to expire."
+            }
+            Self::Server(_) | Self::Other(_) => "Try again later, or try another server.",
+            Self::AddrInUse => "Close other programs using the port, or try again.",
+            Self::PermissionDenied => {
+                "Check whether a firewall or security policy blocks this program."
+            }
+        }
+    }
+}
+
+/// Why a peer could not be relayed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Error)]
+#[serde(tag = "kind", content = "detail", rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum PeerError {
+    /// The TURN server refused to permit the peer (e.g. 403).
+    #[error("Server refused to permit the peer")]
+    Forbidden,
+    /// The local address is already in use.
+    #[error("Local address is already in use")]
+    AddrInUse,
+    /// The local address does not belong to this machine.
+    #[error("Local address is not available")]
+    AddrNotAvailable,
+    /// The local address could not be bound due to missing privileges.
+    #[error("Permission denied")]
+    PermissionDenied,
+    /// The local address is the same as the forward address.
+    #[error("Local address is the forward address")]
+    FwdAddrConflict,
+    /// The local address has no port, so it cannot be pinned.
+    #[error("Local port is not set")]
+    PortRequired,
+    /// Every port of the port range is in use.
+    #[error("Every local port of the range is in use")]
+    PoolExhausted,
+    /// Any other error.
+    #[error("{0}")]
+    Other(String),
+}
+
+impl PeerError {
+    pub(crate) fn classify(error: &anyhow::Error) -> Self {
+        if let Some(e) = error.downcast_ref::<Self>() {
+            return e.clone();
+        }
+
+        if let Some(e) = error.downcast_ref::<io::Error>() {
+            match e.kind() {
+                io::ErrorKind::AddrInUse => return Self::AddrInUse,
+                io::ErrorKind::AddrNotAvailable => return Self::AddrNotAvailable,
+                io::ErrorKind::PermissionDenied => return Self::PermissionDenied,
+                _ => {}
+            }
+        }
+
+        Self::Other(format!("{error:#}"))
+    }
+
+    /// Suggests how the user may fix the error.
+    #[must_use]
+    pub const fn hint(&self) -> &'static str {
+        match self {
+            Self::Forbidden => {
+                "The server does not allow relaying to this address. Check the address of the peer."
+            }
+            Self::AddrInUse => "Pick another local port, or close the program using it.",
+            Self::AddrNotAvailable => "Pick an address of this machine, such as 127.0.0.1.",
+            Self::PermissionDenied => "Pick a port above 1023, or check your firewall.",
+            Self::FwdAddrConflict => "Pick a local port other than the forward address.",
+            Self::PortRequired => "Pick a local port other than 0.",
+            Self::PoolExhausted => "Widen the port range, or disconnect other peers.",
+            Self::Other(_) => "Try again, or pick another local address.",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::anyhow;
+
+    use super::*;
+
+    #[test]
+    fn relay_error_classify() {
+        let cases = [
+            (
+                anyhow!(RelayError::Dns("example.com:3478".into())),
+                RelayError::Dns("example.com:3478".into()),
+            ),
+            (anyhow!("Request timed out"), RelayError::Timeout),
+            (
+                io::Error::from(io::ErrorKind::TimedOut).into(),
+                RelayError::Timeout,
+            ),
+            (anyhow!("Authentication failed"), RelayError::Unauthorized),
+            (
+                anyhow!("Unknown error code from TURN: 438"),
+                RelayError::Unauthorized,
+            ),
+            (anyhow!("Error from TURN: 403"), RelayError::Forbidden),
+            (
+                anyhow!("Unknown error code from TURN: 486"),
+                RelayError::QuotaReached,
+            ),
+            (
+                anyhow!("Unknown error code from TURN: 508"),
+                RelayError::Server(508),
+            ),
+            (
+                io::Error::from(io::ErrorKind::AddrInUse).into(),
+                RelayError::AddrInUse,
+            ),
+            (
+                io::Error::from(io::ErrorKind::PermissionDenied).into(),
+                RelayError::PermissionDenied,
+            ),
+            (
+                anyhow!("Something else").context("Allocating"),
+                RelayError::Other("Allocating: Something else".into()),
+            ),
+        ];
+
+        for (error, expected) in cases {
+            assert_eq!(RelayError::classify(&error), expected, "{error:#}");
+            assert!(!expected.hint().is_empty());
+        }
+    }
+
+    #[test]
+    fn peer_error_classify() {
+        let cases = [
+            (anyhow!(PeerError::PoolExhausted), PeerError::PoolExhausted),
+            (
+                io::Error::from(io::ErrorKind::AddrInUse).into(),
+                PeerError::AddrInUse,
+            ),
+            (
+                io::Error::from(io::ErrorKind::AddrNotAvailable).into(),
+                PeerError::AddrNotAvailable,
+            ),
+            (
+                io::Error::from(io::ErrorKind::PermissionDenied).into(),
+                PeerError::PermissionDenied,
+            ),
+            (
+                anyhow!("Something else"),
+                PeerError::Other("Something else".into()),
+            ),
+        ];
+
+        for (error, expected) in cases {
+            assert_eq!(PeerError::classify(&error), expected, "{error:#}");
+            assert!(!expected.hint().is_empty());
+        }
+    }
+}
@@ -0,0 +1,218 @@
+use std::error::Error;
+use std::fmt::Display;
+use std::future::ready;
+use std::net::{IpAddr, SocketAddr};
+
+use futures::channel::mpsc;
+use futures::{stream, FutureExt, Stream, StreamExt};
+use tokio::sync::broadcast;
+
+use crate::coordinator::{Worker, COMMAND_CHANNEL_CAPACITY, SERVICE_CHANNEL_CAPACITY};
+use crate::{CommandMessage, PeerConfig, PortRange, RelayConfig, RetryPolicy, ServiceMessage};
+
+/// Error returned when a command is sent while the engine is not running.
+#[derive(Debug, Clone)]
+pub struct SendError(pub Box<CommandMessage>);
+
+impl Display for SendError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Relay worker is not running")
+    }
+}
+
+impl Error for SendError {}
+
+/// Sends commands to the relay engine.
+///
+/// Handles are cheap to clone, and all clones control the same engine. The
+/// engine itself runs while the stream returned by [`RelayHandle::events`] is
+/// polled.
+#[derive(Debug, Clone)]
+pub struct RelayHandle {
+    command_snd: broadcast::Sender<CommandMessage>,
+}
+
+impl Default for RelayHandle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RelayHandle {
+    /// Creates a handle. Nothing runs until [`RelayHandle::events`] is called.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            command_snd: broadcast::Sender::new(COMMAND_CHANNEL_CAPACITY),
+        }
+    }
+
+    /// Starts the engine, and returns the stream of its events.
+    ///
+    /// The engine only makes progress while the stream is polled, and the
+    /// stream ends after the engine terminates. Commands sent after this call
+    /// are not lost, even if the stream is not polled yet.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a Tokio runtime.
+    pub fn events(&self) -> impl Stream<Item = ServiceMessage> + Send + Unpin + 'static {
+        let (service_snd, service_rcv) = mpsc::channel(SERVICE_CHANNEL_CAPACITY);
+
+        let worker = Worker::new(self.command_snd.clone(), service_snd).start();
+
+        stream::select(
+            service_rcv,
+            worker.boxed().into_stream().filter_map(|()| ready(None)),
+        )
+    }
+
+    /// Sends a command to the engine.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the engine is not running.
+    pub fn send(&self, command: CommandMessage) -> Result<(), SendError> {
+        self.command_snd
+            .send(command)
+            .map(|_| ())
+            .map_err(|e| SendError(Box::new(e.0)))
+    }
+
+    /// Connects to a TURN server, and requests an allocation.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the engine is not running.
+    pub fn connect_relay(&self, config: RelayConfig) -> Result<(), SendError> {
+        self.send(CommandMessage::ConnectRelay(config))
+    }
+
+    /// Binds a socket for a peer, and requests a permission for it.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the engine is not running.
+    pub fn connect_peer(&self, config: PeerConfig) -> Result<(), SendError> {
+        self.send(CommandMessage::ConnectPeer(config))
+    }
+
+    /// Unbinds the socket of a peer.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the engine is not running.
+    pub fn disconnect_peer(&self, peer_addr: SocketAddr) -> Result<(), SendError> {
+        self.send(CommandMessage::DisconnectPeer(peer_addr))
+    }
+
+    /// Stops relaying the traffic of a peer, while keeping its socket bound
+    /// and its permission alive.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the engine is not running.
+    pub fn pause_peer(&self, peer_addr: SocketAddr) -> Result<(), SendError> {
+        self.send(CommandMessage::PausePeer {
+            peer_addr,
+            paused: true,
+        })
+    }
+
+    /// Resumes relaying the traffic of a paused peer.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the engine is not running.
+    pub fn resume_peer(&self, peer_addr: SocketAddr) -> Result<(), SendError> {
+        self.send(CommandMessage::PausePeer {
+            peer_addr,
+            paused: false,
+        })
+    }
+
+    /// Moves the socket of a peer to another local address, without
+    /// disconnecting it. The new address is reported by
+    /// [`ServiceMessage::PeerBound`](crate::ServiceMessage::PeerBound), or
+    /// else the failure by
+    /// [`ServiceMessage::PeerRebindFailed`](crate::ServiceMessage::PeerRebindFailed).
+    ///
+    /// # Errors
+    ///
+    /// Fails if the engine is not running.
+    pub fn rebind_peer(
+        &self,
+        peer_addr: SocketAddr,
+        local_addr: SocketAddr,
+    ) -> Result<(), SendError> {
+        self.send(CommandMessage::RebindPeer {
+            peer_addr,
+            local_addr,
+        })
+    }
+
+    /// Changes the address that new peers forward to.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the engine is not running.
+    pub fn change_fwd_addr(&self, fwd_addr: SocketAddr) -> Result<(), SendError> {
+        self.send(CommandMessage::ChangeFwdAddr(fwd_addr))
+    }
+
+    /// Changes the address that peers forward to, by an `ip:port` or a
+    /// `host:port` which is resolved periodically.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the engine is not running.
+    pub fn change_fwd_target(&self, target: impl Into<String>) -> Result<(), SendError> {
+        self.send(CommandMessage::ChangeFwdTarget(target.into()))
+    }
+
+    /// Changes the address that new peers bind on, unless they are pinned.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the engine is not running.
+    pub fn change_bind_ip(&self, bind_ip: IpAddr) -> Result<(), SendError> {
+        self.send(CommandMessage::ChangeBindIp(bind_ip))
+    }
+
+    /// Changes the range of ports that new peers bind on, unless they are
+    /// pinned.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the engine is not running.
+    pub fn change_port_range(&self, port_range: Option<PortRange>) -> Result<(), SendError> {
+        self.send(CommandMessage::ChangePortRange(port_range))
+    }
+
+    /// Changes how failed peers are retried, or stops retrying them.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the engine is not running.
+    pub fn change_retry_policy(&self, retry_policy: Option<RetryPolicy>) -> Result<(), SendError> {
+        self.send(CommandMessage::ChangeRetryPolicy(retry_policy))
+    }
+
+    /// Disconnects from the TURN server, and unbinds every peer.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the engine is not running.
+    pub fn disconnect_all(&self) -> Result<(), SendError> {
+        self.send(CommandMessage::DisconnectAll)
+    }
+
+    /// Disconnects everything, and stops the engine.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the engine is not running.
+    pub fn terminate(&self) -> Result<(), SendError> {
+        self.send(CommandMessage::TerminateAll)
+    }
+}
@@ -0,0 +1,62 @@
+//! Relay engine of `turn_relay`.
+//!
+//! A single TURN allocation is shared among many peers. Each peer gets a local
+//! UDP socket; Traffic from the peer arrives on the allocation and is sent
+//! from that socket to the forward address, and replies to that socket are
+//! sent back to the peer through the allocation.
+//!
+//! The engine is controlled by a [`RelayHandle`], and reports back with a
+//! stream of [`ServiceMessage`]s:
+//!
+//! ```no_run
+//! use futures::StreamExt;
+//! use turn_relay_worker::{PeerConfig, RelayConfig, RelayHandle, ServiceMessage};
+//!
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let relay = RelayHandle::new();
+//! let mut events = relay.events();
+//!
+//! relay.connect_relay(RelayConfig::new("turn.example.com", "user", "pass"))?;
+//!
+//! while let Some(event) = events.next().await {
+//!     if let ServiceMessage::RelayAllocated(relay_addr) = event {
+//!         println!("Relay is available at {relay_addr}");
+//!         relay.connect_peer(PeerConfig::new("203.0.113.7:34197".parse()?))?;
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+#![warn(missing_docs)]
+
+mod config;
+mod coordinator;
+mod error;
+mod handle;
+mod log;
+mod macros;
+mod peer;
+mod relay;
+mod types;
+
+use std::net::{IpAddr, SocketAddr};
+
+pub use crate::config::{
+    IdlePolicy, PeerConfig, PortRange, RelayConfig, RetryPolicy, ReturnMode, SourcePolicy,
+};
+pub use crate::error::{PeerError, RelayError};
+pub use crate::handle::{RelayHandle, SendError};
+pub use crate::log::{LogEntry, LogLevel};
+pub use crate::types::{CommandMessage, ServiceMessage, TrafficStats};
+
+/// Unspecified IPv4 address, to bind on all interfaces.
+pub const ALL_IP: IpAddr = addr!(0, 0, 0, 0);
+/// IPv4 loopback address.
+pub const LOCAL_IP: IpAddr = addr!(127, 0, 0, 1);
+/// Any port on all interfaces.
+pub const ALL_DYN_SOCKET: SocketAddr = addr!(ALL_IP:0);
+/// Any port on the loopback interface.
+pub const LOCAL_DYN_SOCKET: SocketAddr = addr!(LOCAL_IP:0);
+/// Address that peers forward to, unless it is changed with
+/// [`CommandMessage::ChangeFwdAddr`] or [`CommandMessage::ChangeFwdTarget`].
+pub const DEFAULT_FWD_SOCKET: SocketAddr = addr!(LOCAL_IP:34197);
@@ -0,0 +1,90 @@
+use std::fmt::Display;
+use std::net::SocketAddr;
+
+use futures::channel::mpsc;
+use serde::Serialize;
+
+use crate::types::ServiceMessage;
+
+/// Severity of a [`LogEntry`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum LogLevel {
+    /// Progress of the engine, e.g. a socket that was bound.
+    #[default]
+    Info,
+    /// Something unexpected that the engine ignored.
+    Warning,
+    /// An operation failed, and the worker carries on.
+    Error,
+    /// A worker stopped because of an error.
+    Fatal,
+}
+
+impl LogLevel {
+    /// Every level, from the least to the most severe.
+    pub const ALL: &[Self] = &[Self::Info, Self::Warning, Self::Error, Self::Fatal];
+}
+
+impl Display for LogLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Info => write!(f, "Info"),
+            Self::Warning => write!(f, "Warning"),
+            Self::Error => write!(f, "Error"),
+            Self::Fatal => write!(f, "Fatal"),
+        }
+    }
+}
+
+/// Diagnostic reported by a worker of the engine.
+///
+/// Entries are also printed to stderr, as `Worker: Level: text`.
+#[derive(Debug, Clone, Serialize)]
+#[non_exhaustive]
+pub struct LogEntry {
+    /// Severity of the entry.
+    pub level: LogLevel,
+    /// Worker that reported the entry, e.g. `Relay`, or `Peer` followed by the
+    /// label and the address of the peer.
+    pub worker: String,
+    /// Peer that the entry is about, if any.
+    pub peer_addr: Option<SocketAddr>,
+    /// Text of the entry.
+    pub text: String,
+}
+
+impl Display for LogEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.level == LogLevel::Info {
+            write!(f, "{}: {}", self.worker, self.text)
+        } else {
+            write!(f, "{}: {}: {}", self.worker, self.level, self.text)
+        }
+    }
+}
+
+impl LogEntry {
+    pub(crate) fn new(
+        level: LogLevel,
+        worker: impl Into<String>,
+        peer_addr: Option<SocketAddr>,
+        text: impl Into<String>,
+    ) -> Self {
+        Self {
+            level,
+            worker: worker.into(),
+            peer_addr,
+            text: text.into(),
+        }
+    }
+
+    /// Prints the entry to stderr, and sends it as a [`ServiceMessage::Log`]
+    /// without waiting. It is dropped if the channel is full.
+    pub(crate) fn report(self, service_snd: &mut mpsc::Sender<ServiceMessage>) {
+        eprintln!("{self}");
+
+        let _ = service_snd.try_send(ServiceMessage::Log(self));
+    }
+}
@@ -0,0 +1,26 @@
+/// Builds an [`IpAddr`](std::net::IpAddr) or a [`SocketAddr`](std::net::SocketAddr) in a const
+/// context.
+///
+/// ```
+/// use std::net::{IpAddr, SocketAddr};
+/// use turn_relay_worker::addr;
+///
+/// const IP: IpAddr = addr!(127, 0, 0, 1);
+/// const SOCKET: SocketAddr = addr!(IP:8080);
+/// assert_eq!(SOCKET, "127.0.0.1:8080".parse().unwrap());
+/// ```
+#[macro_export]
+macro_rules! addr {
+    ($a:tt, $b:tt, $c:tt, $d:tt) => {
+        ::std::net::IpAddr::V4(::std::net::Ipv4Addr::new($a, $b, $c, $d))
+    };
+    ($a:tt, $b:tt, $c:tt, $d:tt:$z:tt) => {
+        ::std::net::SocketAddr::new($crate::addr!($a, $b, $c, $d), $z)
+    };
+    ($a:path:$z:tt) => {
+        ::std::net::SocketAddr::new($a, $z)
+    };
+    (($a:expr):$z:tt) => {
+        ::std::net::SocketAddr::new($a, $z)
+    };
+}
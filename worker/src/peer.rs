@@ -0,0 +1,626 @@
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{bail, ensure};
+use bytes::{Bytes, BytesMut};
+use futures::channel::mpsc;
+use futures::{SinkExt, StreamExt};
+use tokio::net::UdpSocket;
+use tokio::select;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, watch, Notify};
+use tokio::time::{interval, Instant, Interval, MissedTickBehavior};
+use tokio_util::codec::BytesCodec;
+use tokio_util::udp::UdpFramed;
+
+use crate::config::{IdlePolicy, PeerConfig, PortRange, ReturnMode, SourcePolicy};
+use crate::error::PeerError;
+use crate::log::{LogEntry, LogLevel};
+use crate::types::{
+    CommandMessage, DataMessage, OutcomeMessage, ServiceMessage, ToAnyhowResult, ToWorkerErr,
+    TrafficStats, WorkerErr, WorkerOk, WorkerResult, WorkerResultHelper,
+};
+use crate::{addr, LOCAL_DYN_SOCKET};
+
+pub const DROP_REPORT_INTERVAL: Duration = Duration::from_secs(1);
+pub const TRAFFIC_REPORT_INTERVAL: Duration = Duration::from_secs(1);
+pub const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug)]
+pub struct Worker {
+    peer_addr: SocketAddr,
+    name: String,
+    pinned_addr: Option<SocketAddr>,
+    port_range: Option<PortRange>,
+    preferred_port: Option<u16>,
+    fwd_addr: SocketAddr,
+    is_fwd_pinned: bool,
+    fwd_rcv: watch::Receiver<SocketAddr>,
+    resolve_notify: Arc<Notify>,
+    return_mode: ReturnMode,
+    return_addr: Option<SocketAddr>,
+    source_policy: SourcePolicy,
+    drop_count: u64,
+    reported_drop_count: u64,
+    drop_report_interval: Interval,
+    traffic: TrafficStats,
+    reported_traffic: TrafficStats,
+    traffic_report_interval: Interval,
+    idle_policy: Option<IdlePolicy>,
+    last_active: Instant,
+    is_idle: bool,
+    is_paused: bool,
+    idle_check_interval: Interval,
+    upstream_snd: mpsc::Sender<DataMessage>,
+    downstream_rcv: broadcast::Receiver<DataMessage>,
+    command_snd: broadcast::Sender<CommandMessage>,
+    command_rcv: broadcast::Receiver<CommandMessage>,
+    service_snd: mpsc::Sender<ServiceMessage>,
+    outcome_snd: mpsc::Sender<OutcomeMessage>,
+    socket: Option<UdpFramed<BytesCodec>>,
+    local_addr: SocketAddr,
+}
+
+impl Worker {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        config: PeerConfig,
+        fwd_rcv: watch::Receiver<SocketAddr>,
+        resolve_notify: Arc<Notify>,
+        upstream_snd: mpsc::Sender<DataMessage>,
+        downstream_rcv: broadcast::Receiver<DataMessage>,
+        command_snd: broadcast::Sender<CommandMessage>,
+        service_snd: mpsc::Sender<ServiceMessage>,
+        outcome_snd: mpsc::Sender<OutcomeMessage>,
+    ) -> Self {
+        let mut drop_report_interval = interval(DROP_REPORT_INTERVAL);
+        drop_report_interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        let mut traffic_report_interval = interval(TRAFFIC_REPORT_INTERVAL);
+        traffic_report_interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        let mut idle_check_interval = interval(IDLE_CHECK_INTERVAL);
+        idle_check_interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+        let name = config.label.map_or_else(
+            || config.peer_addr.to_string(),
+            |i| format!("{i} ({})", config.peer_addr),
+        );
+
+        Self {
+            peer_addr: config.peer_addr,
+            name,
+            pinned_addr: config.local_addr,
+            port_range: config.port_range,
+            preferred_port: config.preferred_port,
+            fwd_addr: config.fwd_addr.unwrap_or_else(|| *fwd_rcv.borrow()),
+            is_fwd_pinned: config.fwd_addr.is_some(),
+            fwd_rcv,
+            resolve_notify,
+            return_mode: config.return_mode,
+            return_addr: None,
+            source_policy: config.source_policy,
+            drop_count: 0,
+            reported_drop_count: 0,
+            drop_report_interval,
+            traffic: TrafficStats::default(),
+            reported_traffic: TrafficStats::default(),
+            traffic_report_interval,
+            idle_policy: config.idle_policy,
+            last_active: Instant::now(),
+            is_idle: false,
+            is_paused: false,
+            idle_check_interval,
+            upstream_snd,
+            downstream_rcv,
+            command_rcv: command_snd.subscribe(),
+            command_snd,
+            service_snd,
+            outcome_snd,
+            socket: None,
+            local_addr: LOCAL_DYN_SOCKET,
+        }
+    }
+
+    fn log(&mut self, level: LogLevel, text: impl Into<String>) {
+        let worker = format!("Peer {}", self.name);
+        LogEntry::new(level, worker, Some(self.peer_addr), text).report(&mut self.service_snd);
+    }
+
+    /// Whether a socket on the local address would receive its own forwarded
+    /// traffic.
+    fn is_fwd_conflict(&self, local_addr: SocketAddr) -> bool {
+        local_addr.port() == self.fwd_addr.port()
+            && (local_addr.ip() == self.fwd_addr.ip() || local_addr.ip().is_unspecified())
+    }
+
+    /// Binds on the pinned port, or else on the preferred port and then the
+    /// first free port of the range, or else on a dynamic port.
+    async fn bind_socket(&self) -> anyhow::Result<UdpSocket> {
+        let local_addr = self.pinned_addr.unwrap_or(LOCAL_DYN_SOCKET);
+
+        if local_addr.port() != 0 {
+            return Ok(UdpSocket::bind(local_addr).await?);
+        }
+
+        let ports = self
+            .preferred_port
+            .filter(|i| self.port_range.is_none_or(|range| range.contains(*i)))
+            .into_iter()
+            .chain(self.port_range.iter().flat_map(PortRange::ports));
+
+        for port in ports {
+            let local_addr = addr!((local_addr.ip()):port);
+
+            if self.is_fwd_conflict(local_addr) {
+                continue;
+            }
+
+            match UdpSocket::bind(local_addr).await {
+                Ok(i) => return Ok(i),
+                Err(e) if e.kind() == io::ErrorKind::AddrInUse => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        if self.port_range.is_some() {
+            bail!(PeerError::PoolExhausted);
+        }
+
+        Ok(UdpSocket::bind(local_addr).await?)
+    }
+
+    async fn setup_socket(&mut self) -> anyhow::Result<()> {
+        let socket = self.bind_socket().await?;
+
+        self.local_addr = socket.local_addr()?;
+        ensure!(
+            !self.is_fwd_conflict(self.local_addr),
+            PeerError::FwdAddrConflict
+        );
+
+        self.socket = Some(UdpFramed::new(socket, BytesCodec::new()));
+        self.report_bound().await
+    }
+
+    async fn report_bound(&mut self) -> anyhow::Result<()> {
+        self.service_snd
+            .send(ServiceMessage::PeerBound {
+                peer_addr: self.peer_addr,
+                local_addr: self.local_addr,
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    /// Records the source of local traffic as the return address, and tells
+    /// whether its traffic should be relayed.
+    async fn learn_return_addr(&mut self, src: SocketAddr) -> anyhow::Result<bool> {
+        match (self.return_mode, self.return_addr) {
+            (ReturnMode::Forward, _) => return Ok(true),
+            (_, Some(i)) if i == src => return Ok(true),
+            (ReturnMode::LearnLocked, Some(_)) => return Ok(false),
+            _ => {}
+        }
+
+        self.return_addr = Some(src);
+
+        self.log(LogLevel::Info, format!("Learned return address {src}"));
+
+        self.service_snd
+            .send(ServiceMessage::PeerReturnAddr {
+                peer_addr: self.peer_addr,
+                return_addr: src,
+            })
+            .await?;
+
+        Ok(true)
+    }
+
+    async fn handle_socket_message(
+        &mut self,
+        socket_message: Option<Result<(BytesMut, SocketAddr), io::Error>>,
+    ) -> WorkerResult {
+        match socket_message {
+            Some(Ok((data, src))) => {
+                #[cfg(debug_assertions)]
+                eprintln!("Peer {} < {} < {}", self.name, self.local_addr, src);
+
+                if self.is_paused {
+                    return WorkerResult::continued();
+                }
+
+                if !self.source_policy.allows(src, self.fwd_addr)
+                    || !self.learn_return_addr(src).await.into_recoverable()?
+                {
+                    #[cfg(debug_assertions)]
+                    eprintln!("Peer {}: Dropped traffic from {}", self.name, src);
+
+                    self.drop_count += 1;
+
+                    return WorkerResult::continued();
+                }
+
+                let len = data.len() as u64;
+
+                self.upstream_snd
+                    .send((self.peer_addr, data.to_vec()))
+                    .await
+                    .anyhow()
+                    .into_recoverable()?;
+
+                self.traffic.packets_out += 1;
+                self.traffic.bytes_out += len;
+                self.mark_active().await?;
+
+                WorkerResult::continued()
+            }
+
+            Some(Err(e)) => Err(e).anyhow().into_recoverable(),
+
+            None => {
+                self.log(
+                    LogLevel::Warning,
+                    format!("Socket {} is closed", self.local_addr),
+                );
+
+                WorkerResult::terminate()
+            }
+        }
+    }
+
+    async fn handle_relay_message(
+        &mut self,
+        relay_message: Result<(SocketAddr, Vec<u8>), RecvError>,
+    ) -> WorkerResult {
+        let (src, data) = relay_message.anyhow().into_recoverable()?;
+
+        if src == self.peer_addr && !self.is_paused {
+            let dst = self.return_addr.unwrap_or(self.fwd_addr);
+
+            #[cfg(debug_assertions)]
+            eprintln!("Peer {} > {} > {}", self.name, self.local_addr, dst);
+
+            let len = data.len() as u64;
+            let result = self
+                .socket
+                .as_mut()
+                .unwrap()
+                .send((Bytes::from(data), dst))
+                .await;
+
+            // The forward target may have moved to another address.
+            if result.is_err() && dst == self.fwd_addr && !self.is_fwd_pinned {
+                self.resolve_notify.notify_one();
+            }
+
+            result.anyhow().into_recoverable()?;
+
+            self.traffic.packets_in += 1;
+            self.traffic.bytes_in += len;
+            self.mark_active().await?;
+        }
+
+        WorkerResult::continued()
+    }
+
+    async fn report_drop_count(&mut self) -> WorkerResult {
+        self.reported_drop_count = self.drop_count;
+
+        self.service_snd
+            .send(ServiceMessage::PeerDropped {
+                peer_addr: self.peer_addr,
+                count: self.drop_count,
+            })
+            .await
+            .anyhow()
+            .into_recoverable()?;
+
+        WorkerResult::continued()
+    }
+
+    async fn report_traffic(&mut self) -> WorkerResult {
+        self.reported_traffic = self.traffic;
+
+        self.service_snd
+            .send(ServiceMessage::PeerTraffic {
+                peer_addr: self.peer_addr,
+                stats: self.traffic,
+            })
+            .await
+            .anyhow()
+            .into_recoverable()?;
+
+        WorkerResult::continued()
+    }
+
+    async fn mark_active(&mut self) -> Result<(), WorkerErr> {
+        self.last_active = Instant::now();
+
+        if !self.is_idle {
+            return Ok(());
+        }
+
+        self.is_idle = false;
+        self.log(LogLevel::Info, "Active again");
+
+        self.service_snd
+            .send(ServiceMessage::PeerActive(self.peer_addr))
+            .await
+            .anyhow()
+            .into_recoverable()
+    }
+
+    async fn check_idle(&mut self) -> WorkerResult {
+        let Some(idle_policy) = self.idle_policy.filter(|_| !self.is_paused) else {
+            return WorkerResult::continued();
+        };
+
+        let idle_secs = self.last_active.elapsed().as_secs();
+
+        if idle_policy.remove_after.is_some_and(|i| idle_secs >= i) {
+            self.log(
+                LogLevel::Info,
+                format!("Disconnecting after {idle_secs} seconds without traffic"),
+            );
+
+            // Disconnects the peer everywhere, as if the user did, and stops
+            // checking until then.
+            self.idle_policy = None;
+            self.command_snd
+                .send(CommandMessage::DisconnectPeer(self.peer_addr))
+                .anyhow()
+                .into_recoverable()?;
+
+            return WorkerResult::continued();
+        }
+
+        if self.is_idle || idle_secs < idle_policy.idle_after {
+            return WorkerResult::continued();
+        }
+
+        self.is_idle = true;
+        self.log(
+            LogLevel::Info,
+            format!("Idle after {idle_secs} seconds without traffic"),
+        );
+
+        self.service_snd
+            .send(ServiceMessage::PeerIdle(self.peer_addr))
+            .await
+            .anyhow()
+            .into_recoverable()?;
+
+        WorkerResult::continued()
+    }
+
+    fn handle_fwd_addr_change(&mut self) -> WorkerResult {
+        let i = *self.fwd_rcv.borrow_and_update();
+
+        if self.is_fwd_pinned {
+            return WorkerResult::continued();
+        }
+
+        self.fwd_addr = i;
+
+        if self.is_fwd_conflict(self.local_addr) {
+            Err(PeerError::FwdAddrConflict)
+                .anyhow()
+                .into_unrecoverable()?;
+        }
+
+        self.log(
+            LogLevel::Info,
+            format!("Forwarding {} to {i}", self.local_addr),
+        );
+        WorkerResult::continued()
+    }
+
+    async fn pause(&mut self, peer_addr: SocketAddr, paused: bool) -> WorkerResult {
+        if peer_addr != self.peer_addr || self.is_paused == paused {
+            return WorkerResult::continued();
+        }
+
+        self.is_paused = paused;
+        // The time spent paused does not count as idle.
+        self.last_active = Instant::now();
+
+        self.log(LogLevel::Info, if paused { "Paused" } else { "Resumed" });
+
+        self.service_snd
+            .send(if paused {
+                ServiceMessage::PeerPaused(self.peer_addr)
+            } else {
+                ServiceMessage::PeerResumed(self.peer_addr)
+            })
+            .await
+            .anyhow()
+            .into_recoverable()?;
+
+        WorkerResult::continued()
+    }
+
+    async fn bind_pinned(&self, local_addr: SocketAddr) -> anyhow::Result<UdpSocket> {
+        ensure!(local_addr.port() != 0, PeerError::PortRequired);
+        ensure!(
+            !self.is_fwd_conflict(local_addr),
+            PeerError::FwdAddrConflict
+        );
+
+        Ok(UdpSocket::bind(local_addr).await?)
+    }
+
+    /// Moves the socket to a pinned address, or keeps the current one if the
+    /// new one cannot be bound.
+    async fn rebind(&mut self, peer_addr: SocketAddr, local_addr: SocketAddr) -> WorkerResult {
+        if peer_addr != self.peer_addr {
+            return WorkerResult::continued();
+        }
+
+        if local_addr == self.local_addr {
+            self.report_bound().await.into_recoverable()?;
+
+            return WorkerResult::continued();
+        }
+
+        let socket = match self.bind_pinned(local_addr).await {
+            Ok(i) => i,
+            Err(error) => {
+                self.log(
+                    LogLevel::Error,
+                    format!("Failed to rebind on {local_addr}: {error:#}"),
+                );
+
+                self.service_snd
+                    .send(ServiceMessage::PeerRebindFailed {
+                        peer_addr: self.peer_addr,
+                        local_addr,
+                        error: PeerError::classify(&error),
+                    })
+                    .await
+                    .anyhow()
+                    .into_recoverable()?;
+
+                return WorkerResult::continued();
+            }
+        };
+
+        self.local_addr = socket.local_addr().anyhow().into_recoverable()?;
+        self.pinned_addr = Some(local_addr);
+        self.socket = Some(UdpFramed::new(socket, BytesCodec::new()));
+
+        self.log(
+            LogLevel::Info,
+            format!(
+                "Rebound on {}, forwarding to {}",
+                self.local_addr, self.fwd_addr
+            ),
+        );
+
+        self.report_bound().await.into_recoverable()?;
+        // Connecting the peer again, e.g. by a retry, keeps the new address.
+        self.outcome_snd
+            .send(OutcomeMessage::Rebound {
+                peer_addr: self.peer_addr,
+                local_addr,
+            })
+            .await
+            .anyhow()
+            .into_recoverable()?;
+
+        WorkerResult::continued()
+    }
+
+    async fn handle_command_message(
+        &mut self,
+        command_message: Result<CommandMessage, RecvError>,
+    ) -> WorkerResult {
+        match command_message.anyhow().into_recoverable()? {
+            // The peer is connected again, e.g. to retry its permission.
+            CommandMessage::ConnectPeer(config) if config.peer_addr == self.peer_addr => {
+                self.report_bound().await.into_recoverable()?;
+
+                WorkerResult::continued()
+            }
+
+            // The coordinator tells us about the forward address.
+            CommandMessage::ConnectRelay(_)
+            | CommandMessage::ConnectPeer(_)
+            | CommandMessage::ChangeFwdAddr(_)
+            | CommandMessage::ChangeFwdTarget(_)
+            | CommandMessage::ChangeBindIp(_)
+            | CommandMessage::ChangePortRange(_)
+            | CommandMessage::ChangeRetryPolicy(_) => WorkerResult::continued(),
+
+            CommandMessage::DisconnectAll | CommandMessage::TerminateAll => {
+                WorkerResult::terminate()
+            }
+
+            CommandMessage::DisconnectPeer(i) => WorkerResult::terminate_if(self.peer_addr == i),
+
+            CommandMessage::PausePeer { peer_addr, paused } => self.pause(peer_addr, paused).await,
+
+            CommandMessage::RebindPeer {
+                peer_addr,
+                local_addr,
+            } => self.rebind(peer_addr, local_addr).await,
+        }
+    }
+
+    async fn handle_loop(&mut self) -> WorkerResult {
+        select! {
+            socket_message = self.socket.as_mut().unwrap().next() => {
+                self.handle_socket_message(socket_message).await
+            }
+            relay_message = self.downstream_rcv.recv() => {
+                self.handle_relay_message(relay_message).await
+            }
+            command_message = self.command_rcv.recv() => {
+                self.handle_command_message(command_message).await
+            }
+            Ok(()) = self.fwd_rcv.changed() => {
+                self.handle_fwd_addr_change()
+            }
+            _ = self.drop_report_interval.tick(), if self.drop_count != self.reported_drop_count => {
+                self.report_drop_count().await
+            }
+            _ = self.traffic_report_interval.tick(), if self.traffic != self.reported_traffic => {
+                self.report_traffic().await
+            }
+            _ = self.idle_check_interval.tick(), if self.idle_policy.is_some() => {
+                self.check_idle().await
+            }
+        }
+    }
+
+    pub async fn start(mut self) {
+        if let Err(error) = self.setup_socket().await {
+            self.log(LogLevel::Error, format!("Failed to bind: {error:#}"));
+
+            let _ = self
+                .service_snd
+                .send(ServiceMessage::PeerBindFailed {
+                    peer_addr: self.peer_addr,
+                    error: PeerError::classify(&error),
+                })
+                .await;
+            let _ = self
+                .outcome_snd
+                .send(OutcomeMessage::Failed(self.peer_addr))
+                .await;
+
+            return;
+        }
+
+        self.last_active = Instant::now();
+        self.log(
+            LogLevel::Info,
+            format!(
+                "Worker started on {}, forwarding to {}",
+                self.local_addr, self.fwd_addr
+            ),
+        );
+
+        loop {
+            match self.handle_loop().await {
+                Ok(WorkerOk::Continue) => {}
+                Ok(WorkerOk::Terminate) => break,
+                Err(WorkerErr::RecoverableError(error)) => {
+                    self.log(LogLevel::Error, format!("{error:#}"));
+                }
+                Err(WorkerErr::UnrecoverableError(error)) => {
+                    self.log(LogLevel::Fatal, format!("{error:#}"));
+                    break;
+                }
+            }
+        }
+
+        let _ = self
+            .service_snd
+            .send(ServiceMessage::PeerUnbound(self.peer_addr))
+            .await;
+
+        self.log(LogLevel::Info, "Worker stopped");
+    }
+}
@@ -0,0 +1,433 @@
+use std::collections::HashSet;
+use std::net::{SocketAddr, ToSocketAddrs};
+
+use futures::{SinkExt, StreamExt};
+use tokio::net::UdpSocket;
+use tokio::select;
+use tokio::sync::broadcast;
+
+use futures::channel::mpsc;
+use turnclient::{ChannelUsage, MessageFromTurnServer, MessageToTurnServer, TurnClientBuilder};
+
+use crate::config::{PeerConfig, RelayConfig};
+use crate::error::{PeerError, RelayError};
+use crate::log::{LogEntry, LogLevel};
+use crate::types::{
+    CommandMessage, DataMessage, MaybeTurnClient, OutcomeMessage, ServiceMessage, ToAnyhowResult,
+    ToWorkerErr, WorkerErr, WorkerErrHelper, WorkerOk, WorkerResult, WorkerResultHelper,
+};
+use crate::ALL_DYN_SOCKET;
+
+#[derive(Debug)]
+pub struct Worker {
+    upstream_rcv: mpsc::Receiver<DataMessage>,
+    downstream_snd: broadcast::Sender<DataMessage>,
+    command_rcv: broadcast::Receiver<CommandMessage>,
+    service_snd: mpsc::Sender<ServiceMessage>,
+    outcome_snd: mpsc::Sender<OutcomeMessage>,
+    client: MaybeTurnClient,
+    granted_peers: HashSet<String>,
+    is_allocated: bool,
+    will_terminate: bool,
+}
+
+impl Worker {
+    pub fn new(
+        upstream_rcv: mpsc::Receiver<DataMessage>,
+        downstream_snd: broadcast::Sender<DataMessage>,
+        command_rcv: broadcast::Receiver<CommandMessage>,
+        service_snd: mpsc::Sender<ServiceMessage>,
+        outcome_snd: mpsc::Sender<OutcomeMessage>,
+    ) -> Self {
+        Self {
+            upstream_rcv,
+            downstream_snd,
+            command_rcv,
+            service_snd,
+            outcome_snd,
+            client: MaybeTurnClient(None),
+            granted_peers: HashSet::new(),
+            is_allocated: false,
+            will_terminate: false,
+        }
+    }
+
+    fn log(&mut self, level: LogLevel, text: impl Into<String>) {
+        LogEntry::new(level, "Relay", None, text).report(&mut self.service_snd);
+    }
+
+    fn log_peer(&mut self, level: LogLevel, peer_addr: SocketAddr, text: impl Into<String>) {
+        LogEntry::new(level, "Relay", Some(peer_addr), text).report(&mut self.service_snd);
+    }
+
+    async fn grant_peer(&mut self, peer_addr: SocketAddr) -> WorkerResult {
+        self.log_peer(
+            LogLevel::Info,
+            peer_addr,
+            format!("Granted send permission to {peer_addr}"),
+        );
+
+        self.granted_peers.insert(format!("{peer_addr}"));
+        let _ = self
+            .outcome_snd
+            .send(OutcomeMessage::Connected(peer_addr))
+            .await;
+
+        self.service_snd
+            .send(ServiceMessage::RelayPeerGranted(peer_addr))
+            .await
+            .anyhow()
+            .into_recoverable()?;
+
+        WorkerResult::continued()
+    }
+
+    async fn deny_peer(&mut self, peer_addr: SocketAddr) -> WorkerResult {
+        self.log_peer(
+            LogLevel::Warning,
+            peer_addr,
+            format!("Denied send permission to {peer_addr}"),
+        );
+
+        let _ = self
+            .outcome_snd
+            .send(OutcomeMessage::Failed(peer_addr))
+            .await;
+
+        self.service_snd
+            .send(ServiceMessage::RelayPeerDenied {
+                peer_addr,
+                error: PeerError::Forbidden,
+            })
+            .await
+            .anyhow()
+            .into_recoverable()?;
+
+        WorkerResult::continued()
+    }
+
+    async fn handle_turn_message(
+        &mut self,
+        turn_message: Option<Result<MessageFromTurnServer, anyhow::Error>>,
+    ) -> WorkerResult {
+        use MessageFromTurnServer as M;
+
+        match turn_message {
+            Some(Ok(M::AllocationGranted { relay_address, .. })) => {
+                self.log(LogLevel::Info, format!("Available at {relay_address}"));
+
+                self.is_allocated = true;
+
+                self.service_snd
+                    .send(ServiceMessage::RelayAllocated(relay_address))
+                    .await
+                    .anyhow()
+                    .into_unrecoverable()?;
+
+                WorkerResult::continued()
+            }
+
+            Some(Ok(M::RecvFrom(src, data))) => {
+                self.downstream_snd
+                    .send((src, data))
+                    .anyhow()
+                    .into_recoverable()?;
+
+                WorkerResult::continued()
+            }
+
+            Some(Ok(M::RedirectedToAlternateServer(new_addr))) => {
+                self.log(LogLevel::Info, format!("Redirected to {new_addr}"));
+
+                self.service_snd
+                    .send(ServiceMessage::RelayRedirected(new_addr))
+                    .await
+                    .anyhow()
+                    .into_unrecoverable()?;
+
+                WorkerResult::continued()
+            }
+
+            Some(Ok(M::PermissionCreated(peer_addr))) => self.grant_peer(peer_addr).await,
+
+            Some(Ok(M::PermissionNotCreated(peer_addr))) => self.deny_peer(peer_addr).await,
+
+            Some(Ok(M::Disconnected)) => {
+                self.log(LogLevel::Info, "Disconnected");
+
+                self.service_snd
+                    .send(ServiceMessage::RelayDisconnected)
+                    .await
+                    .anyhow()
+                    .into_unrecoverable()?;
+
+                WorkerResult::continued()
+            }
+
+            Some(Ok(M::APacketIsReceivedAndAutomaticallyHandled)) => WorkerResult::continued(),
+
+            Some(Ok(M::ForeignPacket(src, _))) => {
+                #[cfg(debug_assertions)]
+                eprintln!("Relay: Warning: Ignoring an invalid packet from {src}");
+                #[cfg(not(debug_assertions))]
+                let _ = src;
+
+                WorkerResult::continued()
+            }
+
+            Some(Ok(M::NetworkChange)) => {
+                self.log(LogLevel::Warning, "Network changed");
+
+                WorkerResult::continued()
+            }
+
+            Some(Err(e)) if !self.is_allocated => {
+                self.log(LogLevel::Error, format!("Allocation failed: {e:#}"));
+
+                self.client.0 = None;
+                self.signal_connection_error(RelayError::classify(&e))
+                    .await?;
+
+                WorkerResult::terminate_if(self.will_terminate)
+            }
+
+            Some(Err(e)) => Err(e).into_recoverable(),
+
+            None => {
+                self.client.0 = None;
+                self.granted_peers.clear();
+                self.is_allocated = false;
+
+                self.log(LogLevel::Info, "Socket is closed");
+
+                WorkerResult::terminate_if(self.will_terminate)
+            }
+        }
+    }
+
+    async fn handle_peer_message(
+        &mut self,
+        peer_message: Option<(SocketAddr, Vec<u8>)>,
+    ) -> WorkerResult {
+        let (dst, data) = peer_message.unwrap();
+
+        if let Some(client) = &mut self.client.0 {
+            if self.granted_peers.contains(&format!("{dst}")) {
+                client
+                    .send(MessageToTurnServer::SendTo(dst, data))
+                    .await
+                    .into_recoverable()?;
+            }
+        }
+
+        WorkerResult::continued()
+    }
+
+    async fn signal_connection_error(&mut self, error: RelayError) -> WorkerResult {
+        self.service_snd
+            .send(ServiceMessage::RelayConnectionFailed(error))
+            .await
+            .anyhow()
+            .into_unrecoverable()?;
+
+        WorkerResult::continued()
+    }
+
+    async fn connect(
+        &mut self,
+        RelayConfig {
+            server,
+            username,
+            password,
+        }: RelayConfig,
+    ) -> WorkerResult {
+        if self.client.0.is_some() {
+            self.log(
+                LogLevel::Warning,
+                "Ignoring connect request while client is already connected",
+            );
+            return WorkerResult::continued();
+        }
+
+        self.service_snd
+            .send(ServiceMessage::RelayConnecting(server.clone()))
+            .await
+            .anyhow()
+            .into_unrecoverable()?;
+
+        let server = if server.contains(':') {
+            server.to_socket_addrs()
+        } else {
+            (server.as_ref(), 3478).to_socket_addrs()
+        }
+        .ok()
+        .and_then(|mut i| i.next())
+        .ok_or_else(|| RelayError::Dns(server.clone()))
+        .anyhow()
+        .catch_async(|e| self.signal_connection_error(RelayError::classify(e)))
+        .await?;
+
+        self.client.0 = Some(
+            TurnClientBuilder::new(server, username, password).build_and_send_request(
+                UdpSocket::bind(ALL_DYN_SOCKET)
+                    .await
+                    .anyhow()
+                    .catch_async(|e| self.signal_connection_error(RelayError::classify(e)))
+                    .await?,
+            ),
+        );
+
+        self.log(
+            LogLevel::Info,
+            format!("Connected to {server}; Waiting for allocation"),
+        );
+
+        WorkerResult::continued()
+    }
+
+    async fn handle_command_message(
+        &mut self,
+        command_message: Result<CommandMessage, broadcast::error::RecvError>,
+    ) -> WorkerResult {
+        match command_message.anyhow().into_recoverable()? {
+            CommandMessage::ConnectRelay(config) => self.connect(config).await,
+
+            CommandMessage::ConnectPeer(PeerConfig { peer_addr, .. }) => {
+                if let Some(client) = &mut self.client.0 {
+                    if self.granted_peers.contains(&format!("{peer_addr}")) {
+                        self.log_peer(
+                            LogLevel::Info,
+                            peer_addr,
+                            format!("Send permission for {peer_addr} was already granted"),
+                        );
+
+                        self.service_snd
+                            .send(ServiceMessage::RelayPeerGranted(peer_addr))
+                            .await
+                            .anyhow()
+                            .into_recoverable()?;
+                    } else {
+                        LogEntry::new(
+                            LogLevel::Info,
+                            "Relay",
+                            Some(peer_addr),
+                            format!("Requesting send permission for {peer_addr}"),
+                        )
+                        .report(&mut self.service_snd);
+
+                        client
+                            .send(MessageToTurnServer::AddPermission(
+                                peer_addr,
+                                ChannelUsage::WithChannel,
+                            ))
+                            .await
+                            .into_recoverable()?;
+                    }
+                } else {
+                    self.log_peer(
+                        LogLevel::Warning,
+                        peer_addr,
+                        "Ignoring permission request while client is not connected yet",
+                    );
+
+                    self.service_snd
+                        .send(ServiceMessage::RelayDisconnected)
+                        .await
+                        .anyhow()
+                        .into_recoverable()?;
+                }
+
+                WorkerResult::continued()
+            }
+
+            CommandMessage::DisconnectAll => {
+                if let Some(client) = &mut self.client.0 {
+                    LogEntry::new(LogLevel::Info, "Relay", None, "Disconnecting")
+                        .report(&mut self.service_snd);
+
+                    client
+                        .send(MessageToTurnServer::Disconnect)
+                        .await
+                        .into_recoverable()?;
+                }
+
+                WorkerResult::continued()
+            }
+
+            CommandMessage::TerminateAll => {
+                self.will_terminate = true;
+
+                let Some(client) = &mut self.client.0 else {
+                    return WorkerResult::terminate();
+                };
+
+                if !self.is_allocated {
+                    self.log(LogLevel::Info, "Abandoning the pending allocation");
+                    self.client.0 = None;
+                    return WorkerResult::terminate();
+                }
+
+                LogEntry::new(LogLevel::Info, "Relay", None, "Disconnecting")
+                    .report(&mut self.service_snd);
+
+                client
+                    .send(MessageToTurnServer::Disconnect)
+                    .await
+                    .into_unrecoverable()?;
+
+                WorkerResult::continued()
+            }
+
+            CommandMessage::ChangeFwdAddr(..)
+            | CommandMessage::ChangeFwdTarget(..)
+            | CommandMessage::ChangeBindIp(..)
+            | CommandMessage::ChangePortRange(..)
+            | CommandMessage::ChangeRetryPolicy(..)
+            | CommandMessage::PausePeer { .. }
+            | CommandMessage::RebindPeer { .. } => WorkerResult::continued(),
+
+            // The TURN permission cannot be revoked, and the client keeps
+            // refreshing it until the allocation is closed. Only the record of
+            // the grant is dropped, so a reconnected peer requests it again.
+            CommandMessage::DisconnectPeer(peer_addr) => {
+                self.granted_peers.remove(&format!("{peer_addr}"));
+
+                WorkerResult::continued()
+            }
+        }
+    }
+
+    async fn handle_loop(&mut self) -> WorkerResult {
+        select! {
+            turn_message = self.client.next() => {
+                self.handle_turn_message(turn_message).await
+            },
+            peer_message = self.upstream_rcv.next() => {
+                self.handle_peer_message(peer_message).await
+            },
+            command_message = self.command_rcv.recv() => {
+                self.handle_command_message(command_message).await
+            },
+        }
+    }
+
+    pub async fn start(mut self) {
+        self.log(LogLevel::Info, "Worker started");
+
+        loop {
+            match self.handle_loop().await {
+                Ok(WorkerOk::Continue) => {}
+                Ok(WorkerOk::Terminate) => break,
+                Err(WorkerErr::RecoverableError(error)) => {
+                    self.log(LogLevel::Error, format!("{error:#}"));
+                }
+                Err(WorkerErr::UnrecoverableError(error)) => {
+                    self.log(LogLevel::Fatal, format!("{error:#}"));
+                    break;
+                }
+            }
+        }
+
+        self.log(LogLevel::Info, "Worker stopped");
+    }
+}
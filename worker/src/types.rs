@@ -0,0 +1,350 @@
+use std::{
+    error,
+    fmt::Debug,
+    future::Future,
+    net::{IpAddr, SocketAddr},
+};
+
+use futures::{pending, StreamExt};
+use serde::{Deserialize, Serialize};
+use turnclient::{MessageFromTurnServer, TurnClient};
+
+use crate::config::{PeerConfig, PortRange, RelayConfig, RetryPolicy};
+use crate::error::{PeerError, RelayError};
+use crate::log::LogEntry;
+
+/// Event reported by the relay engine.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", content = "data", rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum ServiceMessage {
+    /// Connecting to the given TURN server.
+    RelayConnecting(String),
+    /// The allocation is ready at the given relayed address.
+    RelayAllocated(SocketAddr),
+    /// Disconnected from the TURN server.
+    RelayDisconnected,
+    /// Could not connect to the TURN server, or could not get an allocation.
+    RelayConnectionFailed(RelayError),
+    /// The TURN server redirected us to another server.
+    RelayRedirected(SocketAddr),
+    /// The TURN server permitted the given peer.
+    RelayPeerGranted(SocketAddr),
+    /// The TURN server refused to permit a peer.
+    RelayPeerDenied {
+        /// Address of the peer.
+        peer_addr: SocketAddr,
+        /// Why the peer was refused.
+        error: PeerError,
+    },
+    /// The socket of a peer is bound.
+    PeerBound {
+        /// Address of the peer.
+        peer_addr: SocketAddr,
+        /// Local address of its socket.
+        local_addr: SocketAddr,
+    },
+    /// The socket of a peer could not be bound.
+    PeerBindFailed {
+        /// Address of the peer.
+        peer_addr: SocketAddr,
+        /// Why the socket could not be bound.
+        error: PeerError,
+    },
+    /// The socket of a peer could not be moved to another local address. It
+    /// stays bound on the previous address.
+    PeerRebindFailed {
+        /// Address of the peer.
+        peer_addr: SocketAddr,
+        /// Local address that could not be bound.
+        local_addr: SocketAddr,
+        /// Why the socket could not be moved.
+        error: PeerError,
+    },
+    /// The socket of a peer learned the local address to send the traffic of
+    /// the peer to.
+    PeerReturnAddr {
+        /// Address of the peer.
+        peer_addr: SocketAddr,
+        /// Local address that the traffic of the peer is sent to.
+        return_addr: SocketAddr,
+    },
+    /// The socket of a peer dropped local traffic from disallowed sources. It
+    /// is reported at most once per second.
+    PeerDropped {
+        /// Address of the peer.
+        peer_addr: SocketAddr,
+        /// Number of datagrams dropped since the socket was bound.
+        count: u64,
+    },
+    /// The socket of a peer relayed traffic. It is reported at most once per
+    /// second.
+    PeerTraffic {
+        /// Address of the peer.
+        peer_addr: SocketAddr,
+        /// Traffic relayed since the socket was bound.
+        stats: TrafficStats,
+    },
+    /// The socket of the given peer relayed no traffic for the idle time of
+    /// its [`IdlePolicy`](crate::IdlePolicy).
+    PeerIdle(SocketAddr),
+    /// The socket of the given idle peer relayed traffic again.
+    PeerActive(SocketAddr),
+    /// The socket of the given peer stopped relaying, until it is resumed.
+    PeerPaused(SocketAddr),
+    /// The socket of the given paused peer relays again.
+    PeerResumed(SocketAddr),
+    /// The socket of the given peer is unbound.
+    PeerUnbound(SocketAddr),
+    /// A failed peer is connected again, by the [`RetryPolicy`].
+    PeerRetrying {
+        /// Address of the peer.
+        peer_addr: SocketAddr,
+        /// Number of the retry, counting from 1.
+        attempt: u32,
+    },
+    /// New peers will forward to the given address.
+    FwdAddrChanged(SocketAddr),
+    /// New peers will bind on the given address, unless they are pinned.
+    BindIpChanged(IpAddr),
+    /// New peers will bind on a port of the given range, unless they are
+    /// pinned. A dynamic port is used if it is not set.
+    PortRangeChanged(Option<PortRange>),
+    /// The forward target is resolved to a new address, which every peer now
+    /// forwards to.
+    FwdAddrResolved {
+        /// Forward target, as `host:port`.
+        target: String,
+        /// Address it is resolved to.
+        fwd_addr: SocketAddr,
+    },
+    /// The forward target could not be resolved. Peers keep forwarding to the
+    /// previous address.
+    FwdResolveFailed {
+        /// Forward target, as `host:port`.
+        target: String,
+        /// Why it could not be resolved.
+        error: String,
+    },
+    /// A worker reported a diagnostic.
+    Log(LogEntry),
+}
+
+/// Traffic relayed by the socket of a peer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[non_exhaustive]
+pub struct TrafficStats {
+    /// Number of datagrams received from the peer.
+    pub packets_in: u64,
+    /// Number of bytes received from the peer.
+    pub bytes_in: u64,
+    /// Number of datagrams sent to the peer.
+    pub packets_out: u64,
+    /// Number of bytes sent to the peer.
+    pub bytes_out: u64,
+}
+
+/// Command sent to the relay engine.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "command", content = "data", rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum CommandMessage {
+    /// Connect to a TURN server, and request an allocation.
+    ConnectRelay(RelayConfig),
+    /// Bind a socket for a peer, and request a permission for it.
+    ConnectPeer(PeerConfig),
+    /// Change the address that new peers forward to.
+    ChangeFwdAddr(SocketAddr),
+    /// Change the address that peers forward to, by a `host:port` which is
+    /// resolved periodically, and whenever forwarding fails.
+    ChangeFwdTarget(String),
+    /// Change the address that new peers bind on, unless they are pinned.
+    ChangeBindIp(IpAddr),
+    /// Change the range of ports that new peers bind on, unless they are
+    /// pinned. A dynamic port is used if it is not set.
+    ChangePortRange(Option<PortRange>),
+    /// Change how failed peers are retried. They are not retried if it is not
+    /// set.
+    ChangeRetryPolicy(Option<RetryPolicy>),
+    /// Disconnect from the TURN server, and unbind every peer.
+    DisconnectAll,
+    /// Unbind the socket of the given peer. Its TURN permission stays alive
+    /// until the allocation is closed.
+    DisconnectPeer(SocketAddr),
+    /// Stop or resume relaying the traffic of a peer in both directions. Its
+    /// socket stays bound, and its permission stays alive.
+    PausePeer {
+        /// Address of the peer.
+        peer_addr: SocketAddr,
+        /// Whether to stop relaying, or to resume.
+        paused: bool,
+    },
+    /// Move the socket of a peer to a pinned address. Its permission and its
+    /// return address stay as they are.
+    RebindPeer {
+        /// Address of the peer.
+        peer_addr: SocketAddr,
+        /// Local address to bind on.
+        local_addr: SocketAddr,
+    },
+    /// Disconnect everything, and stop the engine.
+    TerminateAll,
+}
+
+pub type DataMessage = (SocketAddr, Vec<u8>);
+
+/// How a peer turned out, as the coordinator needs to know.
+#[derive(Debug, Clone, Copy)]
+pub enum OutcomeMessage {
+    /// The given peer is set up.
+    Connected(SocketAddr),
+    /// The given peer failed, and may be retried.
+    Failed(SocketAddr),
+    /// The socket of a peer moved to a pinned address.
+    Rebound {
+        peer_addr: SocketAddr,
+        local_addr: SocketAddr,
+    },
+}
+
+#[derive(Debug)]
+pub enum WorkerOk {
+    Continue,
+    Terminate,
+}
+
+#[derive(Debug)]
+pub enum WorkerErr {
+    RecoverableError(anyhow::Error),
+    UnrecoverableError(anyhow::Error),
+}
+
+pub type WorkerResult = Result<WorkerOk, WorkerErr>;
+
+pub trait ToAnyhowResult {
+    type Item;
+    fn anyhow(self) -> Result<Self::Item, anyhow::Error>;
+}
+
+impl<T, E> ToAnyhowResult for Result<T, E>
+where
+    E: error::Error + Send + Sync + 'static,
+{
+    type Item = T;
+
+    fn anyhow(self) -> Result<Self::Item, anyhow::Error> {
+        self.map_err(|e| anyhow::Error::new(e))
+    }
+}
+
+pub trait ToWorkerErr {
+    type Item;
+    fn into_recoverable(self) -> Result<Self::Item, WorkerErr>;
+    fn into_unrecoverable(self) -> Result<Self::Item, WorkerErr>;
+}
+
+impl<T> ToWorkerErr for Result<T, anyhow::Error> {
+    type Item = T;
+
+    fn into_recoverable(self) -> Result<Self::Item, WorkerErr> {
+        self.map_err(WorkerErr::RecoverableError)
+    }
+
+    fn into_unrecoverable(self) -> Result<Self::Item, WorkerErr> {
+        self.map_err(WorkerErr::UnrecoverableError)
+    }
+}
+
+pub trait WorkerResultHelper {
+    fn continued() -> Self;
+    fn terminate() -> Self;
+
+    fn terminate_if(cond: bool) -> Self
+    where
+        Self: Sized,
+    {
+        if cond {
+            Self::terminate()
+        } else {
+            Self::continued()
+        }
+    }
+}
+
+impl WorkerResultHelper for WorkerResult {
+    fn continued() -> Self {
+        Ok(WorkerOk::Continue)
+    }
+
+    fn terminate() -> Self {
+        Ok(WorkerOk::Terminate)
+    }
+}
+
+pub trait WorkerErrHelper {
+    type Result;
+
+    fn catch_async<RR, R, F>(self, f: F) -> impl Future<Output = Self::Result>
+    where
+        F: Send + FnOnce(&anyhow::Error) -> RR,
+        RR: Send + Future<Output = Result<R, WorkerErr>>;
+}
+
+impl<T> WorkerErrHelper for Result<T, WorkerErr>
+where
+    T: Send,
+{
+    type Result = Self;
+
+    async fn catch_async<RR, R, F>(self, f: F) -> Self::Result
+    where
+        F: Send + FnOnce(&anyhow::Error) -> RR,
+        RR: Send + Future<Output = Result<R, WorkerErr>>,
+    {
+        if let Err(WorkerErr::RecoverableError(e) | WorkerErr::UnrecoverableError(e)) = &self {
+            f(e).await?;
+        }
+
+        self
+    }
+}
+
+impl<T> WorkerErrHelper for Result<T, anyhow::Error>
+where
+    T: Send,
+{
+    type Result = Result<T, WorkerErr>;
+
+    #[inline]
+    async fn catch_async<RR, R, F>(self, f: F) -> Self::Result
+    where
+        F: Send + FnOnce(&anyhow::Error) -> RR,
+        RR: Send + Future<Output = Result<R, WorkerErr>>,
+    {
+        self.into_recoverable().catch_async(f).await
+    }
+}
+
+pub struct MaybeTurnClient(pub Option<TurnClient>);
+
+impl MaybeTurnClient {
+    pub async fn next(&mut self) -> Option<Result<MessageFromTurnServer, anyhow::Error>> {
+        if let Some(client) = &mut self.0 {
+            client.next().await
+        } else {
+            loop {
+                pending!();
+                eprintln!("Relay: Warning: Client was polled again but it is not connected yet");
+            }
+        }
+    }
+}
+
+impl Debug for MaybeTurnClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            Some(_) => write!(f, "MaybeTurnClient(Some)"),
+            None => write!(f, "MaybeTurnClient(None)"),
+        }
+    }
+}